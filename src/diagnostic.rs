@@ -0,0 +1,90 @@
+use crate::lexing::Span;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A single error prepared for display, with the line of source it occurred on.
+///
+/// Rendered like
+/// ```text
+/// error: Expected Dot, found EOF
+///  --> -e:1:3
+///   |
+/// 1 | \x
+///   |   ^
+///   = hint: did you mean `\x.`? The binders of an abstraction end with a dot
+/// ```
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub file: Option<String>,
+    pub span: Option<Span>,
+    /// The line of source the span starts on, if the source is known.
+    pub source_line: Option<String>,
+    pub notes: Vec<String>,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    /// The line of `source` that `span` starts on, counting lines from 1.
+    pub fn line_of(source: &str, span: Span) -> Option<String> {
+        if span.is_none() || span.line < 1 { return None; }
+        source.lines().nth(span.line as usize - 1).map(str::to_string)
+    }
+
+    /// Renders the diagnostic, highlighted with ANSI escape codes if `colour` is set.
+    pub fn render(&self, colour: bool) -> String {
+        let paint = |code: &str, text: &str| if colour { format!("{}{}{}", code, text, RESET) } else { text.to_string() };
+        let mut out = format!("{}: {}", paint(RED, "error"), paint(BOLD, &self.message));
+
+        let location = match (&self.file, self.span) {
+            (Some(file), Some(span)) => Some(format!("{}:{}", file, span)),
+            (Some(file), None) => Some(file.clone()),
+            (None, Some(span)) => Some(span.to_string()),
+            (None, None) => None,
+        };
+        let gutter = match (self.span, &self.source_line) {
+            (Some(span), Some(_)) => span.line.to_string().len(),
+            _ => 0,
+        };
+        let margin = " ".repeat(gutter);
+        if let Some(location) = location {
+            out += &format!("\n{}{} {}", margin, paint(BLUE, "-->"), location);
+        }
+
+        if let (Some(span), Some(line)) = (self.span, &self.source_line) {
+            let bar = paint(BLUE, "|");
+            let (offset, width) = underline(line, span);
+            out += &format!("\n{} {}", margin, bar);
+            out += &format!("\n{} {}", paint(BLUE, &span.line.to_string()), bar);
+            if !line.is_empty() { out += &format!(" {}", line); }
+            out += &format!("\n{} {} {}{}", margin, bar, " ".repeat(offset), paint(RED, &"^".repeat(width)));
+        }
+
+        for note in &self.notes {
+            out += &format!("\n{} {} {}: {}", margin, paint(BLUE, "="), paint(BOLD, "note"), note);
+        }
+        if let Some(hint) = &self.hint {
+            out += &format!("\n{} {} {}: {}", margin, paint(BLUE, "="), paint(BOLD, "hint"), hint);
+        }
+        out
+    }
+}
+
+// The column the underline starts at and its width, in characters, limited to the line
+// as spans of statements can run over several lines. Empty spans, e.g. at the end of the input,
+// are underlined by a single caret after the line.
+fn underline(line: &str, span: Span) -> (usize, usize) {
+    let length = line.chars().count();
+    let offset = (span.col.max(1) as usize - 1).min(length);
+    let mut bytes = 0;
+    let width = line.chars().skip(offset)
+        .take_while(|c| {
+            bytes += c.len_utf8();
+            bytes <= span.end - span.start
+        })
+        .count();
+    (offset, width.max(1))
+}
@@ -0,0 +1,184 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use crate::diagnostic::Diagnostic;
+use crate::evaluating::EvalError;
+use crate::lexing::{LexError, Span};
+use crate::parsing::{Notation, ParseError};
+
+/// Any error of the interpreter, by the phase it occurred in.
+///
+/// `Display` renders errors briefly as `[file:]line:col: message`, the position omitted
+/// if there is none, and several errors one per line, terms always in ASCII. `render` shows them with their source.
+#[derive(Debug)]
+pub enum Error {
+    Lex(LexError),
+    Parse(ParseError),
+    Eval(EvalError),
+    /// A source file could not be read.
+    Io { path: String, error: io::Error },
+    /// A statement of a loaded file that is not a binding.
+    ExpectedBinding { span: Span },
+    /// Input that should have been a single expression, with the number of statements found.
+    StatementCount { found: usize },
+    /// An error reducing the statement at `span`, the definition of `name` if it is a binding.
+    Statement { name: Option<String>, span: Span, error: Box<Error> },
+    /// An error in the source file `file`, along with its contents.
+    File { file: String, source: String, error: Box<Error> },
+    /// Every error found, e.g. in a file that is loaded, in order.
+    Multiple(Vec<Error>),
+}
+
+impl Error {
+    /// A single error as itself, otherwise all of them.
+    pub fn from_all(mut errors: Vec<Error>) -> Error {
+        match errors.len() {
+            1 => errors.remove(0),
+            _ => Error::Multiple(errors),
+        }
+    }
+
+    pub fn in_file(self, file: &str, source: &str) -> Error {
+        Error::File { file: file.to_string(), source: source.to_string(), error: Box::new(self) }
+    }
+
+    /// Where in the source the error occurred, if anywhere.
+    pub fn span(&self) -> Option<Span> {
+        let span = match self {
+            Error::Lex(error) => Some(error.span()),
+            Error::Parse(error) => Some(error.span()),
+            Error::Eval(error) => error.span(),
+            Error::ExpectedBinding { span } | Error::Statement { span, .. } => Some(*span),
+            Error::File { error, .. } => error.span(),
+            Error::Io { .. } | Error::StatementCount { .. } | Error::Multiple(_) => None,
+        };
+        span.filter(|span| !span.is_none())
+    }
+
+    /// Every single error, flattening `Multiple`, with the file each occurred in and its source.
+    pub fn errors(&self) -> Vec<(Option<(&str, &str)>, &Error)> {
+        let mut errors = Vec::new();
+        self.collect(None, &mut errors);
+        errors
+    }
+
+    fn collect<'a>(&'a self, file: Option<(&'a str, &'a str)>, errors: &mut Vec<(Option<(&'a str, &'a str)>, &'a Error)>) {
+        match self {
+            Error::File { file, source, error } => error.collect(Some((file, source)), errors),
+            Error::Multiple(all) => all.iter().for_each(|error| error.collect(file, errors)),
+            _ => errors.push((file, self)),
+        }
+    }
+
+    /// Every single error prepared for display. Positions outside any file refer to `source`,
+    /// terms in messages are shown in `notation`.
+    pub fn diagnostics(&self, source: Option<&str>, notation: Notation) -> Vec<Diagnostic> {
+        self.errors().into_iter().map(|(file, error)| {
+            let span = error.span();
+            let source = file.map(|(_, source)| source).or(source);
+            Diagnostic {
+                message: error.message(notation),
+                file: file.map(|(file, _)| file.to_string()),
+                span,
+                source_line: span.zip(source).and_then(|(span, source)| Diagnostic::line_of(source, span)),
+                notes: error.notes(),
+                hint: error.hint(),
+            }
+        }).collect()
+    }
+
+    /// Renders every error with the line of source it occurred on, see `Diagnostic`.
+    pub fn render(&self, source: Option<&str>, colour: bool, notation: Notation) -> String {
+        self.diagnostics(source, notation).iter()
+            .map(|diagnostic| diagnostic.render(colour))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    fn notes(&self) -> Vec<String> {
+        match self {
+            Error::Parse(error) => error.notes(),
+            Error::Eval(error) => error.notes(),
+            Error::ExpectedBinding { .. } => vec!["loaded files may only contain bindings".to_string()],
+            Error::Statement { error, .. } | Error::File { error, .. } => error.notes(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            Error::Lex(error) => error.hint(),
+            Error::Parse(error) => error.hint(),
+            Error::Eval(error) => error.hint(),
+            Error::ExpectedBinding { .. } => Some("evaluate expressions with the run command instead".to_string()),
+            Error::Statement { error, .. } | Error::File { error, .. } => error.hint(),
+            _ => None,
+        }
+    }
+
+    // The description of a single error, without its position
+    fn message(&self, notation: Notation) -> String {
+        match self {
+            Error::Lex(error) => error.to_string(),
+            Error::Parse(error) => error.to_string(),
+            Error::Eval(error) => error.message(notation),
+            Error::Io { path, error } => format!("{}: {}", path, error),
+            Error::ExpectedBinding { .. } => "Expected a binding of the form Name = expr".to_string(),
+            Error::StatementCount { found: 0 } => "Expected an expression".to_string(),
+            Error::StatementCount { found } => format!("Expected a single expression, found {} statements", found),
+            Error::Statement { name: Some(name), error, .. } => format!("{}: {}", name, error.message(notation)),
+            Error::Statement { name: None, error, .. } => error.message(notation),
+            Error::File { error, .. } => error.message(notation),
+            Error::Multiple(errors) => errors.iter().map(|error| error.message(notation)).collect::<Vec<_>>().join("\n"),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        for (i, (file, error)) in self.errors().into_iter().enumerate() {
+            if i > 0 { writeln!(f)?; }
+            if let Some((file, _)) = file { write!(f, "{}:", file)?; }
+            if let Some(span) = error.span() { write!(f, "{}: ", span)?; }
+            write!(f, "{}", error.message(Notation::Ascii))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Lex(error) => Some(error),
+            Error::Parse(error) => Some(error),
+            Error::Eval(error) => Some(error),
+            Error::Io { error, .. } => Some(error),
+            Error::Statement { error, .. } | Error::File { error, .. } => Some(&**error),
+            Error::ExpectedBinding { .. } | Error::StatementCount { .. } | Error::Multiple(_) => None,
+        }
+    }
+}
+
+impl From<LexError> for Error {
+    fn from(error: LexError) -> Error {
+        Error::Lex(error)
+    }
+}
+
+impl From<Vec<LexError>> for Error {
+    fn from(errors: Vec<LexError>) -> Error {
+        Error::from_all(errors.into_iter().map(Error::Lex).collect())
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Error {
+        Error::Parse(error)
+    }
+}
+
+impl From<EvalError> for Error {
+    fn from(error: EvalError) -> Error {
+        Error::Eval(error)
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::{Display, Error, Formatter};
+
+use crate::evaluating::{Evaluator, Term};
+use crate::parsing::Expr;
+
+// A value a normal form encodes, terms may encode more than one
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Encoding {
+    // \f.\x.f (f ... (f x)), applying f n times
+    Numeral(usize),
+    // \x.\y.x is true and \x.\y.y is false
+    Boolean(bool),
+    // \c.\n.c a (c b n) is [a, b], elements are shown as the value they encode if any
+    List(Vec<Term>),
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Encoding::Numeral(n) => write!(f, "{}", n),
+            Encoding::Boolean(b) => write!(f, "{}", b),
+            Encoding::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    match decode(element).first() {
+                        Some(encoding) => write!(f, "{}", encoding)?,
+                        None => write!(f, "{}", element.to_expr())?,
+                    }
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Evaluator {
+    // Every encoding the expression is alpha-equivalent to, none if it is not expanded
+    pub fn decode(expr: &Expr) -> Vec<Encoding> {
+        if !expr.is_expanded() { return Vec::new(); }
+        decode(&Term::from_expr(expr))
+    }
+}
+
+fn decode(term: &Term) -> Vec<Encoding> {
+    let mut encodings = Vec::new();
+    if let Some(n) = decode_numeral(term) {
+        encodings.push(Encoding::Numeral(n));
+    }
+    if let Some(b) = decode_boolean(term) {
+        encodings.push(Encoding::Boolean(b));
+    }
+    if let Some(elements) = decode_list(term) {
+        encodings.push(Encoding::List(elements));
+    }
+    encodings
+}
+
+fn decode_numeral(term: &Term) -> Option<usize> {
+    let mut body = match term {
+        Term::Abstraction(_, body) => match body.as_ref() {
+            Term::Abstraction(_, body) => body.as_ref(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let mut n = 0;
+    // f is bound one abstraction further out than x
+    while let Term::Application(f, arg) = body {
+        if **f != Term::Var(1) { return None; }
+        n += 1;
+        body = arg;
+    }
+    match body {
+        Term::Var(0) => Some(n),
+        _ => None,
+    }
+}
+
+fn decode_boolean(term: &Term) -> Option<bool> {
+    match term {
+        Term::Abstraction(_, body) => match body.as_ref() {
+            Term::Abstraction(_, body) => match body.as_ref() {
+                Term::Var(1) => Some(true),
+                Term::Var(0) => Some(false),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn decode_list(term: &Term) -> Option<Vec<Term>> {
+    let mut body = match term {
+        Term::Abstraction(_, body) => match body.as_ref() {
+            Term::Abstraction(_, body) => body.as_ref(),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let mut elements = Vec::new();
+    while let Term::Application(left, tail) = body {
+        match left.as_ref() {
+            // Elements may not refer to c or n, and are moved out from under both
+            Term::Application(cons, element)
+            if **cons == Term::Var(1) && !element.references(0) && !element.references(1) => {
+                elements.push(element.shift(-2, 0));
+            }
+            _ => return None,
+        }
+        body = tail;
+    }
+    match body {
+        Term::Var(0) => Some(elements),
+        _ => None,
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::{Display, Error, Formatter};
+
+use crate::evaluating::{Evaluator, Limit, ReductionStrategy, Term};
+use crate::parsing::Expr;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Equivalence {
+    Equivalent,
+    Distinct,
+    // A limit was hit before both sides reached a normal form
+    Unknown(Limit),
+}
+
+impl Display for Equivalence {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Equivalence::Equivalent => write!(f, "equivalent"),
+            Equivalence::Distinct => write!(f, "not equivalent"),
+            Equivalence::Unknown(limit) => write!(f, "unknown ({})", limit),
+        }
+    }
+}
+
+impl Evaluator {
+    // Equal up to renaming of bound variables, both expressions are expected to be expanded
+    pub fn alpha_equivalent(left: &Expr, right: &Expr) -> bool {
+        Term::from_expr(left) == Term::from_expr(right)
+    }
+
+    // Expects a desugared expression
+    pub fn eta_expand(expr: &Expr) -> Expr {
+        Term::from_expr(expr).eta_expand().to_expr()
+    }
+
+    /* Compares normal forms, which are unique up to alpha-equivalence by Church-Rosser,
+    so distinct normal forms mean the terms are not beta-equivalent.
+    With eta enabled these are beta-eta normal forms, deciding beta-eta-equivalence instead.
+    Normal order is used regardless of the current strategy, as it finds the normal form
+    whenever one exists, but this can still only be decided within the limits.
+    Alpha-equivalent terms are equivalent without reducing them, which matters for
+    recursive bindings, as they have no normal form and only ever reach a limit.
+    */
+    pub fn beta_equivalent(&mut self, left: &Expr, right: &Expr) -> Equivalence {
+        if Evaluator::alpha_equivalent(left, right) {
+            return Equivalence::Equivalent;
+        }
+        let strategy = self.strategy;
+        self.strategy = ReductionStrategy::NormalOrder;
+        let (left, _, left_limit) = self.reduce(Term::from_expr(left), None);
+        let (right, _, right_limit) = self.reduce(Term::from_expr(right), None);
+        self.strategy = strategy;
+
+        match left_limit.or(right_limit) {
+            Some(limit) => Equivalence::Unknown(limit),
+            None if left == right => Equivalence::Equivalent,
+            None => Equivalence::Distinct,
+        }
+    }
+}
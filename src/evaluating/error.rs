@@ -0,0 +1,75 @@
+use std::fmt::{Display, Error, Formatter};
+
+use crate::evaluating::Limit;
+use crate::lexing::Span;
+use crate::parsing::{Expr, Notation};
+
+#[derive(Debug, Clone)]
+pub enum EvalError {
+    // A metavariable without a binding, where it occurs or where the first binding
+    // it is reached through occurs, with the bindings it is reached through outermost first
+    UndefinedMetavariable { name: String, through: Vec<String>, span: Span },
+    // Reduction hit the fuel or depth limit, with the term it stopped at
+    LimitReached { limit: Limit, steps: usize, expr: Expr },
+    // Binding a name again that deferred bindings refer to, which would change their meaning
+    Rebinding { name: String, dependents: Vec<String>, span: Span },
+}
+
+impl EvalError {
+    // Errors of reduction have no single position in the source
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EvalError::UndefinedMetavariable { span, .. } | EvalError::Rebinding { span, .. } => Some(*span),
+            EvalError::LimitReached { .. } => None,
+        }
+    }
+
+    // Context that explains the error
+    pub fn notes(&self) -> Vec<String> {
+        match self {
+            EvalError::UndefinedMetavariable { name, through, .. } if !through.is_empty() =>
+                vec![format!("{} is referenced through the definition of {}", name, through.join(", then "))],
+            EvalError::Rebinding { name, dependents, .. } =>
+                vec![format!("{} {} to {} by name", dependents.join(", "), if dependents.len() == 1 { "refers" } else { "refer" }, name)],
+            _ => Vec::new(),
+        }
+    }
+
+    // A suggestion of how to fix the error
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            EvalError::UndefinedMetavariable { name, .. } => Some(format!("bind it before it is used, e.g. {} = \\x.x", name)),
+            EvalError::LimitReached { limit: Limit::Steps(_), .. } =>
+                Some("the term may have no normal form, otherwise raise the limit with :fuel or --fuel, or try another strategy".to_string()),
+            EvalError::LimitReached { limit: Limit::Depth(_), .. } => Some("raise the limit with :depth".to_string()),
+            EvalError::LimitReached { limit: Limit::Size(_), .. } =>
+                Some("the term may have no normal form, otherwise raise the limit with :size".to_string()),
+            EvalError::Rebinding { .. } => Some("bind the new definition to another name".to_string()),
+        }
+    }
+}
+
+impl EvalError {
+    // The description of the error, with any term in it shown in notation
+    pub fn message(&self, notation: Notation) -> String {
+        match self {
+            EvalError::LimitReached { limit: limit @ Limit::Steps(_), expr, .. } =>
+                format!("{}, stopped at: {}", limit, expr.notated(notation)),
+            EvalError::LimitReached { limit, steps, expr } =>
+                format!("{} after {} steps, stopped at: {}", limit, steps, expr.notated(notation)),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            EvalError::UndefinedMetavariable { name, .. } => write!(f, "Undefined metavariable: {}", name),
+            EvalError::Rebinding { name, .. } => write!(f, "Cannot bind {} again, other bindings depend on it", name),
+            EvalError::LimitReached { .. } => write!(f, "{}", self.message(Notation::Ascii)),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
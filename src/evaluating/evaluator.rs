@@ -1,218 +1,431 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 
-use crate::{format_error, force_evaluate};
+use crate::force_evaluate;
+use crate::evaluating::{EvalError, Limit, Normalization, Observer, ReductionStrategy, Term};
+use crate::evaluating::readback::NameIndex;
 use crate::parsing::Expr;
-use crate::lexing::Token;
+use crate::lexing::{Span, Token};
 
 pub struct Evaluator {
-    pub env: HashMap<String, Expr>,
-    pub names: HashSet<String>
+    // Only changed through insert, which keeps names up to date
+    pub(crate) env: HashMap<String, Expr>,
+    pub strategy: ReductionStrategy,
+    // Whether eta redexes are contracted along with beta redexes
+    pub eta: bool,
+    // Maximum number of reduction steps, None reduces until a normal form is reached
+    pub fuel: Option<usize>,
+    // Whether results show subterms equal to a binding by the binding's name, or no names at all
+    pub fold: bool,
+    // Terms nested deeper than this are not reduced any further, which protects the stack
+    pub max_depth: usize,
+    // Terms with more nodes than this are not reduced any further, which bounds memory use
+    pub max_size: usize,
+    // Shared thunks of call by need, only live during a single reduction
+    pub(crate) heap: Vec<Term>,
+    // Names the input being evaluated binds later on, bindings may refer to them before that
+    pub(crate) declared: HashSet<String>,
+    // Bindings indexed for folding into names, built when first needed
+    pub(crate) names: RefCell<Option<NameIndex>>,
 }
 
 impl Evaluator {
+    pub const DEFAULT_FUEL: usize = 10_000;
+    pub const DEFAULT_MAX_DEPTH: usize = 1_000;
+    pub const DEFAULT_MAX_SIZE: usize = 10_000;
+
     pub fn new() -> Evaluator {
+        Evaluator::with_strategy(ReductionStrategy::default())
+    }
+
+    pub fn with_strategy(strategy: ReductionStrategy) -> Evaluator {
         Evaluator {
             env: Evaluator::generate_default_env(),
-            names: HashSet::new()
+            strategy,
+            eta: false,
+            fuel: Some(Evaluator::DEFAULT_FUEL),
+            fold: true,
+            max_depth: Evaluator::DEFAULT_MAX_DEPTH,
+            max_size: Evaluator::DEFAULT_MAX_SIZE,
+            heap: Vec::new(),
+            declared: HashSet::new(),
+            names: RefCell::new(None),
         }
     }
 }
 
+impl Default for Evaluator {
+    fn default() -> Evaluator {
+        Evaluator::new()
+    }
+}
+
 impl Evaluator {
 
-    pub fn evaluate(&mut self, expression: Expr) -> Result<Expr, String> {
-        self.names.clear();
-        let expr = self.expand_bindings(&expression)?;
-        println!("Expanded (parenthesized): {:?}", expr);
-        println!("Expanded: {}", expr);
-//        println!("names: {:?}", self.names);
-        self.beta_reduce(expr)
+    pub fn evaluate(&mut self, expression: Expr) -> Result<Expr, EvalError> {
+        if let Expr::Binding(name, expr, span) = &expression {
+            return self.bind(name, expr, *span);
+        }
+        self.check_bound(&expression)?;
+        self.beta_reduce(expression)
     }
 
-    fn expand_bindings(&mut self, expr: &Expr) -> Result<Expr, String> {
-        match expr {
-            Expr::Variable(name) => {
-                self.names.insert(name.clone());
-                Ok(expr.clone())
-            },
-            Expr::Abstraction(name, expr) => {
-                self.names.insert(name.clone());
-                Ok(Expr::Abstraction(name.to_string(), Box::new(self.expand_bindings(expr)?)))
+    /* Binds name to the value of expr, returning that value. The value is stored with the
+    bindings it refers to expanded, so binding those names again later does not change it.
+    Bindings that refer to themselves, directly or through other bindings, have no normal form
+    to store, and bindings referring to declared names not bound yet cannot be expanded. Both are bound
+    unevaluated instead, and references to them are tied through a fixed point once expanded.
+    Values keep referring to these by name, so the fixed point suits the strategy they are used with.
+    */
+    pub fn bind(&mut self, name: &str, expr: &Expr, span: Span) -> Result<Expr, EvalError> {
+        if self.depends_on(expr, name, &mut Vec::new()) {
+            self.check_rebinding(name, expr, span)?;
+            self.insert(name, expr.clone());
+            return Ok(expr.clone());
+        }
+        self.check_bound(expr)?;
+        let expr = self.bind_nested(expr)?;
+        let (term, steps, limit) = self.reduce(Term::from_expr(&self.desugar(&expr)), None);
+        let value = self.output(&term);
+        if let Some(limit) = limit {
+            return Err(EvalError::LimitReached { limit, steps, expr: value });
+        }
+        // Names are only folded in for output, the binding itself could otherwise end up referring to itself
+        let captured = self.capture(&term).to_expr();
+        self.check_rebinding(name, &captured, span)?;
+        self.insert(name, captured);
+        Ok(value)
+    }
+
+    // Expands references to bindings, except deferred ones. Other bindings cannot recur, so this terminates
+    fn capture(&self, term: &Term) -> Term {
+        match term {
+            Term::Global(name) => match self.env.get(name) {
+                Some(definition) if !self.depends_on(definition, name, &mut Vec::new()) =>
+                    self.capture(&Term::from_expr(&self.desugar(definition))),
+                _ => term.clone(),
             },
-            Expr::Application(left, right) => Ok(Expr::Application(
-                Box::new(self.expand_bindings(left)?),
-                Box::new(self.expand_bindings(right)?),
+            Term::Abstraction(hint, body) => Term::Abstraction(hint.clone(), Box::new(self.capture(body))),
+            Term::Application(left, right) => Term::Application(Box::new(self.capture(left)), Box::new(self.capture(right))),
+            _ => term.clone(),
+        }
+    }
+
+    fn insert(&mut self, name: &str, definition: Expr) {
+        self.env.insert(name.to_string(), definition);
+        *self.names.get_mut() = None;
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) {
+        self.env.remove(name);
+        *self.names.get_mut() = None;
+    }
+
+    pub fn env(&self) -> &HashMap<String, Expr> {
+        &self.env
+    }
+
+    /* Deferred bindings keep referring to other bindings by name, so binding a name one of them
+    refers to again would change its meaning, unless the definition stays the same up to renaming.
+    */
+    fn check_rebinding(&self, name: &str, definition: &Expr, span: Span) -> Result<(), EvalError> {
+        let previous = match self.env.get(name) {
+            Some(previous) => previous,
+            None => return Ok(()),
+        };
+        let mut dependents: Vec<String> = self.env.iter()
+            .filter(|(other, other_definition)| *other != name && other_definition.refers_to(name))
+            .map(|(other, _)| other.clone())
+            .collect();
+        if dependents.is_empty() || self.same_definition(name, previous, definition) {
+            return Ok(());
+        }
+        dependents.sort();
+        Err(EvalError::Rebinding { name: name.to_string(), dependents, span })
+    }
+
+    fn same_definition(&self, name: &str, left: &Expr, right: &Expr) -> bool {
+        let term = |definition: &Expr| self.expand_definition(name, definition, Span::NONE, &mut Vec::new())
+            .map(|expanded| Term::from_expr(&self.desugar(&expanded)));
+        match (term(left), term(right)) {
+            (Ok(left), Ok(right)) => left == right,
+            _ => false,
+        }
+    }
+
+    // Binds every binding nested in expr, e.g. (X = I) y or X = Y = I, innermost first,
+    // and replaces it with its value
+    pub fn bind_nested(&mut self, expr: &Expr) -> Result<Expr, EvalError> {
+        match expr {
+            Expr::Variable(_, _) | Expr::MetaVariable(_) => Ok(expr.clone()),
+            Expr::Abstraction(name, body, span) => Ok(Expr::Abstraction(name.clone(), Box::new(self.bind_nested(body)?), *span)),
+            Expr::Application(left, right, span) => Ok(Expr::Application(
+                Box::new(self.bind_nested(left)?),
+                Box::new(self.bind_nested(right)?),
+                *span,
+            )),
+            Expr::Grouping(expr, span) => Ok(Expr::Grouping(Box::new(self.bind_nested(expr)?), *span)),
+            Expr::Binding(name, expr, span) => Ok(Expr::Grouping(Box::new(self.bind(name, expr, *span)?), *span)),
+            Expr::Let(name, value, body, span) => Ok(Expr::Let(
+                name.clone(),
+                Box::new(self.bind_nested(value)?),
+                Box::new(self.bind_nested(body)?),
+                *span,
             )),
-            Expr::Grouping(expr) => Ok(Expr::Grouping(Box::new(self.expand_bindings(expr)?))),
-            Expr::Binding(x, expr) => Ok(Expr::Binding(x.clone(), Box::new(self.expand_bindings(expr)?))),
+            Expr::LetRec(name, value, body, span) => Ok(Expr::LetRec(
+                name.clone(),
+                Box::new(self.bind_nested(value)?),
+                Box::new(self.bind_nested(body)?),
+                *span,
+            )),
+        }
+    }
+
+    // Fails on the first metavariable that is not bound, in expr or the bindings it refers to
+    pub fn check_bound(&self, expr: &Expr) -> Result<(), EvalError> {
+        self.check_bound_in(expr, &mut Vec::new())
+    }
+
+    fn check_bound_in(&self, expr: &Expr, visited: &mut Vec<String>) -> Result<(), EvalError> {
+        match expr {
+            Expr::Variable(_, _) => Ok(()),
+            // Deferred bindings may refer to names bound later
+            Expr::Binding(x, body, _) if self.depends_on(body, x, &mut Vec::new()) => Ok(()),
+            Expr::Abstraction(_, expr, _) | Expr::Grouping(expr, _) | Expr::Binding(_, expr, _) => self.check_bound_in(expr, visited),
+            Expr::Application(left, right, _) | Expr::Let(_, left, right, _) | Expr::LetRec(_, left, right, _) => {
+                self.check_bound_in(left, visited)?;
+                self.check_bound_in(right, visited)
+            }
             Expr::MetaVariable(token) => {
-                let expr = Expr::Grouping(
-                    Box::new(self.evaluate_meta_variable(&token)?)
-                );
-                self.expand_bindings(&expr)
+                if visited.contains(&token.lexeme) { return Ok(()); }
+                visited.push(token.lexeme.clone());
+                let definition = self.evaluate_meta_variable(token)?;
+                self.check_bound_in(&definition, visited).map_err(|error| referenced_through(error, token))
             }
         }
     }
 
-    pub fn beta_reduce(&mut self, expression: Expr) -> Result<Expr, String> {
-        match expression {
-            Expr::Abstraction(name, expr) => Ok(Expr::Abstraction(name, Box::new(self.beta_reduce(*expr.clone())?))),
-            // Expr::Abstraction(_, _) => Ok(expression),
-            Expr::Application(ref left, ref right) => self.reduce_application(&left, &right),
-            Expr::Grouping(expr) => self.beta_reduce(*expr),
-            Expr::Variable(_) => Ok(expression),
-            Expr::Binding(name, expr) => {
-                // Recursively evaluates/binds inner expressions
-                let value = self.beta_reduce(*expr);
-                self.env.insert(name, value.clone()?);
-                value
-            },
-            Expr::MetaVariable(_) => unreachable!("Beta-reducing metavariable")
-
+    // Whether expanding expr reaches name or a declared metavariable that is not bound yet
+    fn depends_on(&self, expr: &Expr, name: &str, visited: &mut Vec<String>) -> bool {
+        match expr {
+            // Nested bindings are evaluated, and deferred if need be, on their own
+            Expr::Variable(_, _) | Expr::Binding(_, _, _) => false,
+            Expr::Abstraction(_, expr, _) | Expr::Grouping(expr, _) => self.depends_on(expr, name, visited),
+            Expr::Application(left, right, _) | Expr::Let(_, left, right, _) | Expr::LetRec(_, left, right, _) =>
+                self.depends_on(left, name, visited) || self.depends_on(right, name, visited),
+            Expr::MetaVariable(token) => {
+                if token.lexeme == name { return true; }
+                if visited.contains(&token.lexeme) { return false; }
+                visited.push(token.lexeme.clone());
+                match self.env.get(&token.lexeme) {
+                    Some(definition) => self.depends_on(definition, name, visited),
+                    None => self.declared.contains(&token.lexeme),
+                }
+            }
         }
     }
 
-    fn evaluate_meta_variable(&mut self, token: &Token) -> Result<Expr, String> {
-        match self.env.get(&token.lexeme) {
-            Some(expr) => Ok(expr.clone()),
-            None => Err(format_error(&format!("Undefined metavariable: {}", token.lexeme), token))
+    /* Replaces every metavariable with its binding and desugars lets, which reduction
+    does not need as it unfolds metavariables itself, but comparing terms does.
+    */
+    pub fn expand(&self, expression: &Expr) -> Result<Expr, EvalError> {
+        let expanded = self.expand_bindings(expression, &mut Vec::new())?;
+        Ok(self.desugar(&expanded))
+    }
+
+    /* Recursive bindings are expanded lazily: a metavariable that is already being expanded
+    becomes a variable of the same name, and the expansion it recurs into is abstracted over
+    that variable and passed to a fixed point combinator, e.g. F = G F -> Y (\f.G f).
+    Variables never start with an upper case letter, so the variable is renamed to a fresh one once tied.
+    Mutually recursive bindings are tied from the outermost one inwards.
+    Nodes of the expression keep their spans, while every node of an expanded definition
+    spans the metavariable it replaces, as positions within the definition refer to another input.
+    */
+    fn expand_bindings(&self, expr: &Expr, in_progress: &mut Vec<String>) -> Result<Expr, EvalError> {
+        match expr {
+            Expr::Variable(_, _) => Ok(expr.clone()),
+            Expr::Abstraction(name, expr, span) =>
+                Ok(Expr::Abstraction(name.to_string(), Box::new(self.expand_bindings(expr, in_progress)?), *span)),
+            Expr::Application(left, right, span) => Ok(Expr::Application(
+                Box::new(self.expand_bindings(left, in_progress)?),
+                Box::new(self.expand_bindings(right, in_progress)?),
+                *span,
+            )),
+            Expr::Grouping(expr, span) => Ok(Expr::Grouping(Box::new(self.expand_bindings(expr, in_progress)?), *span)),
+            // Deferred bindings are bound as written
+            Expr::Binding(x, body, _) if self.depends_on(body, x, &mut Vec::new()) => Ok(expr.clone()),
+            Expr::Binding(x, expr, span) => Ok(Expr::Binding(x.clone(), Box::new(self.expand_bindings(expr, in_progress)?), *span)),
+            Expr::Let(x, value, body, span) => Ok(Expr::Let(
+                x.clone(),
+                Box::new(self.expand_bindings(value, in_progress)?),
+                Box::new(self.expand_bindings(body, in_progress)?),
+                *span,
+            )),
+            Expr::LetRec(x, value, body, span) => Ok(Expr::LetRec(
+                x.clone(),
+                Box::new(self.expand_bindings(value, in_progress)?),
+                Box::new(self.expand_bindings(body, in_progress)?),
+                *span,
+            )),
+            Expr::MetaVariable(token) => {
+                let (name, span) = (&token.lexeme, token.span);
+                if in_progress.contains(name) {
+                    return Ok(Expr::Variable(name.clone(), span));
+                }
+                let definition = self.evaluate_meta_variable(token)?;
+                self.expand_definition(name, &definition, span, in_progress)
+                    .map_err(|error| referenced_through(error, token))
+            }
         }
     }
 
-    // Reducible form: (\x.E) N
-    // (\x.E) N reduces to
-    // E[x->N]
-//    pub fn beta_reduce(&self, expression: &Expr) -> Result<Expr, String> {
-//        match expression {
-//            Expr::Abstraction(_, _) => Ok(expression.clone()),
-//            Expr::Application(ref left, ref right) => self.beta_reduce(&self.reduce_application(&left, &right)?),
-//            Expr::Grouping(ref expr) => self.beta_reduce(expr),
-//            Expr::Variable(_) => Ok(expression.clone()),
-//            _ => Err("".to_string())
-//        }
-//    }
-
-
-    // pub fn evaluate(&self, expression: Expr) -> Result<Expr, String> {
-    //     if let Expr::Application(left, right) = expression {
-    //         if let Expr::Abstraction(name, expr) = *left {
-    //             self.substitute(&expr, &name, *right)
-    //         } else {
-    //             Ok(*left)
-    //         }
-    //     } else {
-    //         Ok(expression)
-    //     }
-    // }
-
-    fn reduce_application(&mut self, left: &Expr, right: &Expr) -> Result<Expr, String> {
-        // if expr is redex
-        if let Expr::Abstraction(name, expr) = self.beta_reduce(left.clone())? {
-            let substitution = self.substitute(&expr, &name, right)?;
-            self.beta_reduce(substitution)
-        } else {
-            Ok(Expr::Application(Box::new(self.beta_reduce(left.clone())?), Box::new(self.beta_reduce(right.clone())?)))
+    // The definition of name expanded where it is referenced at span, tied through a fixed point if it recurs
+    fn expand_definition(&self, name: &str, definition: &Expr, span: Span, in_progress: &mut Vec<String>) -> Result<Expr, EvalError> {
+        in_progress.push(name.to_string());
+        let expanded = self.expand_bindings(&definition.respan(span), in_progress);
+        in_progress.pop();
+        let expanded = expanded?;
+        if !expanded.occurs_free(name) {
+            return Ok(Expr::Grouping(Box::new(expanded), span));
         }
+        let binder = expanded.fresh_name(&binder_hint(name));
+        Ok(Expr::Grouping(Box::new(Expr::Application(
+            Box::new(self.fixed_point(span)),
+            Box::new(Expr::Abstraction(binder.clone(), Box::new(expanded.rename_free(name, &binder)), span)),
+            span,
+        )), span))
     }
 
-    /* Assuming no naming issues
-    E[x->N]
-    x[x->N] = N
-    (E1 E2)[x->N] = E1[x->N] E2[x->N]
-    (\y.E)[x->N] \y.E[x->N]
+    /* Rewrites lets into the redexes they stand for,
+    let x = e1 in e2 -> (\x.e2) e1 and letrec f = e in b -> (\f.b) (Y (\f.e)), with Z for strict strategies.
+    The nodes introduced span the let they replace.
+    Nested bindings only stand for their value here, bind_nested binds them before evaluation.
     */
+    pub fn desugar(&self, expr: &Expr) -> Expr {
+        match expr {
+            Expr::Variable(_, _) | Expr::MetaVariable(_) => expr.clone(),
+            Expr::Abstraction(name, body, span) => Expr::Abstraction(name.clone(), Box::new(self.desugar(body)), *span),
+            Expr::Application(left, right, span) =>
+                Expr::Application(Box::new(self.desugar(left)), Box::new(self.desugar(right)), *span),
+            Expr::Grouping(expr, span) => Expr::Grouping(Box::new(self.desugar(expr)), *span),
+            Expr::Binding(_, expr, span) => Expr::Grouping(Box::new(self.desugar(expr)), *span),
+            Expr::Let(name, value, body, span) => Expr::Application(
+                Box::new(Expr::Grouping(Box::new(Expr::Abstraction(name.clone(), Box::new(self.desugar(body)), *span)), *span)),
+                Box::new(Expr::Grouping(Box::new(self.desugar(value)), value.span())),
+                *span,
+            ),
+            Expr::LetRec(name, value, body, span) => Expr::Application(
+                Box::new(Expr::Grouping(Box::new(Expr::Abstraction(name.clone(), Box::new(self.desugar(body)), *span)), *span)),
+                Box::new(Expr::Grouping(Box::new(Expr::Application(
+                    Box::new(self.fixed_point(*span)),
+                    Box::new(Expr::Abstraction(name.clone(), Box::new(self.desugar(value)), *span)),
+                    *span,
+                )), *span)),
+                *span,
+            ),
+        }
+    }
 
-    // Doesn't consider bound variables etc...
-//    pub fn substitute(&self, expression: &Expr, var: &str, with: &Expr) -> Result<Expr, String> {
-//        match expression {
-//            Expr::Variable(name) => {
-//                if name == var { Ok(with.clone()) } else { Ok(expression.clone()) }
-//            }
-//            Expr::Application(left, right) =>
-//                Ok(Expr::Application(
-//                    Box::new(self.substitute(left, var, with)?),
-//                    Box::new(self.substitute(right, var, with)?),
-//                )),
-//            Expr::Grouping(expr) =>
-//                self.substitute(expr, var, with),
-//            Expr::Abstraction(name, expr) =>
-//                Ok(Expr::Abstraction(name.to_string(), Box::new(self.substitute(expr, var, with)?))),
-//            _ => unreachable!(),
-//        }
-//    }
-
-    pub fn substitute(&mut self, expression: &Expr, var: &str, with: &Expr) -> Result<Expr, String> {
+    // Y diverges when its argument is reduced first, Z delays the self application behind an abstraction.
+    // Spans the recursion it ties
+    fn fixed_point(&self, span: Span) -> Expr {
+        let combinator = if self.strategy.is_strict() {
+            force_evaluate(r#"\f.(\x.f (\v.x x v)) (\x.f (\v.x x v))"#)
+        } else {
+            force_evaluate(r#"\f.(\x.f (x x)) (\x.f (x x))"#)
+        };
+        combinator.respan(span)
+    }
+
+    // Reduces according to the current strategy until no redex is left
+    pub fn beta_reduce(&mut self, expression: Expr) -> Result<Expr, EvalError> {
         match expression {
-            Expr::Variable(name) => {
-                if name == var { Ok(with.clone()) } else { Ok(expression.clone()) }
+            Expr::Binding(name, expr, span) => self.bind(&name, &expr, span),
+            _ => {
+                let expr = self.bind_nested(&expression)?;
+                self.normalize(&expr).into_result()
             }
-            Expr::Application(left, right) =>
-                Ok(Expr::Application(
-                    Box::new(self.substitute(left, var, with)?),
-                    Box::new(self.substitute(right, var, with)?),
-                )),
-            Expr::Grouping(expr) => self.substitute(expr, var, with),
-            Expr::Abstraction(name, expr) => {
-                if name == var { // Don't substitute bound variables
-                    Ok(expression.clone())  
-                } else if !Evaluator::is_free(expr, name) {
-                    Ok(Expr::Abstraction(name.to_string(), Box::new(self.substitute(expr, var, with)?)))
-                } else {
-                    // name is free in expr thus require rename
-                    let new_name = self.generate_name();
-                    let new_expr = Evaluator::alpha_rename(expr, name, &new_name);
-                    Ok(Expr::Abstraction(new_name, Box::new(self.substitute(&new_expr, var, with)?)))
-                }
-            },
-            _ => unreachable!(),
         }
     }
 
-    fn generate_name(&mut self) -> String {
-        for c in "abcdefghijklmnopqrstuvwxyz".chars() {
-            let name = char::to_string(&c);
-//            println!("{} in {:?}", name, self.names);
-            if self.names.contains(&name) { continue; }
-            self.names.insert(name.clone());
-            return name
+    // Like beta_reduce, but hitting the fuel or depth limit is not an error
+    pub fn normalize(&mut self, expression: &Expr) -> Normalization {
+        self.normalize_with(expression, None)
+    }
+
+    pub fn normalize_with(&mut self, expression: &Expr, observer: Option<&mut Observer>) -> Normalization {
+        let (term, steps, limit) = self.reduce(Term::from_expr(&self.desugar(expression)), observer);
+        let expr = self.output(&term);
+        match limit {
+            Some(limit) => Normalization::Incomplete { expr, steps, limit },
+            None => Normalization::Normal { expr, steps },
         }
-        panic!("Ran out of variable names")
     }
 
-    // Alpha conversion
-    pub fn alpha_rename(expression: &Expr, from: &String, to: &String) -> Expr {
-        match expression {
-            Expr::Variable(name) => if name == from { Expr::Variable(to.clone()) } else { expression.clone() },
-            Expr::Application(left, right) => Expr::Application(
-                Box::new(Evaluator::alpha_rename(left, from, to)),
-                Box::new(Evaluator::alpha_rename(right, from, to)),
-            ),
-            Expr::Grouping(expr) => Expr::Grouping(Box::new(Evaluator::alpha_rename(expr, from, to))),
-            Expr::Abstraction(name, expr) => if name == from {
-                Expr::Abstraction(to.clone(), Box::new(Evaluator::alpha_rename(expr, from, to)))
-            } else {
-                Expr::Abstraction(name.clone(), Box::new(Evaluator::alpha_rename(expr, from, to)))
-            },
-            Expr::Binding(name, expr) => Expr::Binding(name.clone(), Box::new(Evaluator::alpha_rename(expr, from, to))),
-            _ => unreachable!("Alpha renaming metavariable or binding")
+    // A term as it is shown, with subterms equal to a binding folded into its name if enabled,
+    // otherwise with every binding it still refers to expanded
+    fn output(&self, term: &Term) -> Expr {
+        if self.fold { self.fold_names(term).to_expr() } else { self.expand_globals(term, &mut HashMap::new()).to_expr() }
+    }
 
+    // Reduces until the strategy's normal form or a limit is reached,
+    // returning the last term, the number of steps taken, and the limit hit if any
+    pub fn reduce(&mut self, term: Term, mut observer: Option<&mut Observer>) -> (Term, usize, Option<Limit>) {
+        self.heap.clear();
+        let mut term = term;
+        let mut steps = 0;
+        loop {
+            if let Some(limit) = self.exceeded_limit(&term) {
+                return (self.read_back(&term), steps, Some(limit));
+            }
+            if let Some(fuel) = self.fuel {
+                if steps >= fuel {
+                    return (self.read_back(&term), steps, Some(Limit::Steps(fuel)));
+                }
+            }
+            // Read back before stepping, as call by need updates thunks in place
+            let current = observer.as_ref().map(|_| self.read_back(&term).to_expr());
+            let (next, redex) = match self.contract_next(&term) {
+                Some(contraction) => contraction,
+                None => break,
+            };
+            steps += 1;
+            if let (Some(observer), Some(current)) = (observer.as_mut(), current) {
+                observer(steps, &current, &redex);
+            }
+            term = next;
         }
+        (self.read_back(&term), steps, None)
     }
 
-    /* x is free in E if
-    E = x
-    E = \y.E' and x is free in E'
-    */
-    pub fn is_free(expression: &Expr, var: &str) -> bool {
-        match expression {
-            Expr::Variable(_) => true,
-            Expr::Abstraction(name, expr) => name != var && Evaluator::is_free(expr, var),
-            Expr::Application(left, right) => Evaluator::is_free(left, var) || Evaluator::is_free(right, var),
-            Expr::Grouping(expr) => Evaluator::is_free(expr, var),
-            _ => unreachable!("Checking free variable in binding or metavariable")
+    fn evaluate_meta_variable(&self, token: &Token) -> Result<Expr, EvalError> {
+        match self.env.get(&token.lexeme) {
+            Some(expr) => Ok(expr.clone()),
+            None => Err(EvalError::UndefinedMetavariable { name: token.lexeme.clone(), through: Vec::new(), span: token.span }),
         }
     }
+}
 
+// Positions in a definition belong to the input it was bound from, so an undefined metavariable
+// in one is reported where the definition is referenced instead
+fn referenced_through(error: EvalError, token: &Token) -> EvalError {
+    match error {
+        EvalError::UndefinedMetavariable { name, mut through, .. } => {
+            through.insert(0, token.lexeme.clone());
+            EvalError::UndefinedMetavariable { name, through, span: token.span }
+        }
+        error => error,
+    }
+}
+
+// The binding's name in lower case as the variable a recursion is tied through, e.g. FACT -> fact,
+// or f if that does not make a variable
+fn binder_hint(name: &str) -> String {
+    let hint = name.trim_start_matches('$').to_lowercase();
+    match hint.chars().next() {
+        Some(c) if c == '_' || (c.is_alphabetic() && !c.is_uppercase() && c != 'λ') => hint,
+        _ => "f".to_string(),
+    }
 }
 
 macro_rules! map(
@@ -232,12 +445,58 @@ impl Evaluator {
     fn generate_default_env() -> HashMap<String, Expr> {
         let identity = force_evaluate(r#"\x.x"#);
         let mockingbird = force_evaluate(r#"\f.f f"#);
-        let cardinal = force_evaluate(r#"\fab.f b a"#);
-        let kestrel = force_evaluate(r#"\xy.x"#);
-        let kite = force_evaluate(r#"\xy.y"#);
-        let bluebird = force_evaluate(r#"\fgh.f (g h)"#); // Function composition
-        let thrush = force_evaluate(r#"\fg.g f"#);
-        let not = force_evaluate(r#"\b.b (\xy.y) (\xy.x)"#);
+        let cardinal = force_evaluate(r#"\f a b.f b a"#);
+        let kestrel = force_evaluate(r#"\x y.x"#);
+        let kite = force_evaluate(r#"\x y.y"#);
+        // Fixed point combinators, Y only for strategies that do not reduce arguments first
+        let y = force_evaluate(r#"\f.(\x.f (x x)) (\x.f (x x))"#);
+        let z = force_evaluate(r#"\f.(\x.f (\v.x x v)) (\x.f (\v.x x v))"#);
+        let theta = force_evaluate(r#"(\x y.y (x x y)) (\x y.y (x x y))"#); // Turing's
+        let bluebird = force_evaluate(r#"\f g h.f (g h)"#); // Function composition
+        let thrush = force_evaluate(r#"\f g.g f"#);
+
+        // Church booleans select one of two arguments
+        let truth = force_evaluate(r#"\x y.x"#);
+        let falsity = force_evaluate(r#"\x y.y"#);
+        let not = force_evaluate(r#"\b.b (\x y.y) (\x y.x)"#);
+        let and = force_evaluate(r#"\p q.p q p"#);
+        let or = force_evaluate(r#"\p q.p p q"#);
+        let xor = force_evaluate(r#"\p q.p (q (\x y.y) (\x y.x)) q"#);
+        let if_then_else = force_evaluate(r#"\b t f.b t f"#);
+        let beq = force_evaluate(r#"\p q.p q (q (\x y.y) (\x y.x))"#);
+
+        // Arithmetic on Church numerals
+        let succ = force_evaluate(r#"\n f x.f (n f x)"#);
+        let plus = force_evaluate(r#"\m n f x.m f (n f x)"#);
+        let mult = force_evaluate(r#"\m n f.m (n f)"#);
+        let exp = force_evaluate(r#"\m n.n m"#);
+        let pred = force_evaluate(r#"\n f x.n (\g h.h (g f)) (\u.x) (\u.u)"#);
+        let sub = force_evaluate(r#"\m n.n (\n f x.n (\g h.h (g f)) (\u.x) (\u.u)) m"#); // Truncated at 0
+        let iszero = force_evaluate(r#"\n.n (\x a b.b) (\a b.a)"#);
+
+        // Church pairs and lists, a list is its own right fold as in [a, b] -> \c n.c a (c b n)
+        let pair = force_evaluate(r#"\a b f.f a b"#);
+        let fst = force_evaluate(r#"\p.p (\a b.a)"#);
+        let snd = force_evaluate(r#"\p.p (\a b.b)"#);
+        let nil = force_evaluate(r#"\c n.n"#);
+        let cons = force_evaluate(r#"\h t c n.c h (t c n)"#);
+        let head = force_evaluate(r#"\l.l (\h t.h) (\x.x)"#); // I for NIL
+        let tail = force_evaluate(r#"\l c n.l (\h t g.g h (t c)) (\t.n) (\h t.t)"#);
+        let isnil = force_evaluate(r#"\l.l (\h t a b.b) (\a b.a)"#);
+        let map = force_evaluate(r#"\f l c n.l (\h t.c (f h) t) n"#);
+        let fold = force_evaluate(r#"\f z l.l f z"#);
+        let length = force_evaluate(r#"\l f x.l (\h t.f t) x"#);
+
+        // Scott encodings, which are their own case analysis rather than their own fold
+        let snil = force_evaluate(r#"\n c.n"#);
+        let scons = force_evaluate(r#"\h t n c.c h t"#);
+        let shead = force_evaluate(r#"\l.l (\x.x) (\h t.h)"#);
+        let stail = force_evaluate(r#"\l.l (\n c.n) (\h t.t)"#);
+        let sisnil = force_evaluate(r#"\l.l (\a b.a) (\h t a b.b)"#);
+        let szero = force_evaluate(r#"\z s.z"#);
+        let ssucc = force_evaluate(r#"\n z s.s n"#);
+        let spred = force_evaluate(r#"\n.n (\z s.z) (\p.p)"#);
+        let siszero = force_evaluate(r#"\n.n (\a b.a) (\p a b.b)"#);
 
         map! {
             "I".to_string() => identity,
@@ -247,36 +506,46 @@ impl Evaluator {
             "KI".to_string() => kite,
             "B".to_string() => bluebird,
             "T".to_string() => thrush,
-            "NOT".to_string() => not
+            "Y".to_string() => y,
+            "Z".to_string() => z,
+            "THETA".to_string() => theta.clone(),
+            "Θ".to_string() => theta,
+            "TRUE".to_string() => truth,
+            "FALSE".to_string() => falsity,
+            "NOT".to_string() => not,
+            "AND".to_string() => and,
+            "OR".to_string() => or,
+            "XOR".to_string() => xor,
+            "IF".to_string() => if_then_else,
+            "BEQ".to_string() => beq,
+            "SUCC".to_string() => succ,
+            "PLUS".to_string() => plus,
+            "MULT".to_string() => mult,
+            "EXP".to_string() => exp,
+            "PRED".to_string() => pred,
+            "SUB".to_string() => sub,
+            "ISZERO".to_string() => iszero,
+            "PAIR".to_string() => pair,
+            "FST".to_string() => fst,
+            "SND".to_string() => snd,
+            "NIL".to_string() => nil,
+            "CONS".to_string() => cons,
+            "HEAD".to_string() => head,
+            "TAIL".to_string() => tail,
+            "ISNIL".to_string() => isnil,
+            "MAP".to_string() => map,
+            "FOLD".to_string() => fold,
+            "LENGTH".to_string() => length,
+            "SNIL".to_string() => snil,
+            "SCONS".to_string() => scons,
+            "SHEAD".to_string() => shead,
+            "STAIL".to_string() => stail,
+            "SISNIL".to_string() => sisnil,
+            "SZERO".to_string() => szero,
+            "SSUCC".to_string() => ssucc,
+            "SPRED".to_string() => spred,
+            "SISZERO".to_string() => siszero
         }
     }
 
 }
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
-
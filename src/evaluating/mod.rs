@@ -1,4 +1,18 @@
+pub use encoding::Encoding;
+pub use equivalence::Equivalence;
+pub use error::EvalError;
 pub use evaluator::Evaluator;
+pub use normalization::{Limit, Normalization};
+pub use reduction::{Observer, Redex, RedexKind, Step};
+pub use strategy::ReductionStrategy;
+pub use term::Term;
 
+pub mod encoding;
+pub mod equivalence;
+pub mod error;
 pub mod evaluator;
-
+pub mod normalization;
+pub mod readback;
+pub mod strategy;
+pub mod term;
+mod reduction;
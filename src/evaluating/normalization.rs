@@ -0,0 +1,57 @@
+use std::fmt::{Display, Error, Formatter};
+
+use crate::evaluating::EvalError;
+use crate::parsing::Expr;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Limit {
+    // Maximum number of reduction steps
+    Steps(usize),
+    // Maximum nesting depth of the term being reduced
+    Depth(usize),
+    // Maximum number of nodes of the term being reduced
+    Size(usize),
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            Limit::Steps(n) => write!(f, "Did not reach normal form within {} steps", n),
+            Limit::Depth(n) => write!(f, "Did not reach normal form, term exceeded maximum depth of {}", n),
+            Limit::Size(n) => write!(f, "Did not reach normal form, term exceeded maximum size of {} nodes", n),
+        }
+    }
+}
+
+// Result of reducing a term as far as the evaluator's limits allow
+#[derive(Debug, Clone)]
+pub enum Normalization {
+    Normal { expr: Expr, steps: usize },
+    // The partially reduced term at the point a limit was hit
+    Incomplete { expr: Expr, steps: usize, limit: Limit },
+}
+
+impl Normalization {
+    pub fn expr(&self) -> &Expr {
+        match self {
+            Normalization::Normal { expr, .. } | Normalization::Incomplete { expr, .. } => expr,
+        }
+    }
+
+    pub fn steps(&self) -> usize {
+        match self {
+            Normalization::Normal { steps, .. } | Normalization::Incomplete { steps, .. } => *steps,
+        }
+    }
+
+    pub fn is_normal(&self) -> bool {
+        matches!(self, Normalization::Normal { .. })
+    }
+
+    pub fn into_result(self) -> Result<Expr, EvalError> {
+        match self {
+            Normalization::Normal { expr, .. } => Ok(expr),
+            Normalization::Incomplete { expr, steps, limit } => Err(EvalError::LimitReached { limit, steps, expr }),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::evaluating::{Evaluator, Term};
+use crate::lexing::{Span, Token, TokenType};
+use crate::parsing::Expr;
+
+// Bindings by the hash of their fully expanded terms, for the strictness of strategy they were expanded for,
+// as that decides the fixed point recursive bindings are tied through
+pub(crate) struct NameIndex {
+    strict: bool,
+    names: HashMap<u64, Vec<(Term, String)>>,
+}
+
+// The hash of a term and of each of its subterms, computed bottom up so every node is hashed once.
+// Equal terms have equal hashes, names of abstractions are ignored like in Term's Hash
+enum Hashed {
+    Leaf(u64),
+    Abstraction(u64, Box<Hashed>),
+    Application(u64, Box<Hashed>, Box<Hashed>),
+}
+
+impl Hashed {
+    fn of(term: &Term) -> Hashed {
+        let mut hasher = DefaultHasher::new();
+        match term {
+            Term::Abstraction(_, body) => {
+                let body = Hashed::of(body);
+                (1u8, body.hash()).hash(&mut hasher);
+                Hashed::Abstraction(hasher.finish(), Box::new(body))
+            }
+            Term::Application(left, right) => {
+                let (left, right) = (Hashed::of(left), Hashed::of(right));
+                (2u8, left.hash(), right.hash()).hash(&mut hasher);
+                Hashed::Application(hasher.finish(), Box::new(left), Box::new(right))
+            }
+            _ => {
+                (0u8, term).hash(&mut hasher);
+                Hashed::Leaf(hasher.finish())
+            }
+        }
+    }
+
+    fn hash(&self) -> u64 {
+        match self {
+            Hashed::Leaf(hash) | Hashed::Abstraction(hash, _) | Hashed::Application(hash, _, _) => *hash,
+        }
+    }
+}
+
+impl NameIndex {
+    fn get(&self, term: &Term, hashed: &Hashed) -> Option<&str> {
+        self.names.get(&hashed.hash())?.iter()
+            .find(|(indexed, _)| indexed == term)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+impl Evaluator {
+    /* Replaces subterms alpha-equivalent to a binding by the binding's name, outermost first.
+    Bindings are indexed by their fully expanded de Bruijn terms, so every subterm takes
+    a single hash lookup however large the environment is. Subterms are looked up
+    expanded as well, and names already in the term are kept as they are.
+    The index is built once and kept until the bindings or the strategy change.
+    Of several bindings for the same term the shortest name wins, e.g. \x.\y.y is KI rather than FALSE,
+    though ASCII names win over others, so aliases like Θ do not show up in ASCII output.
+    */
+    pub fn fold_names(&self, term: &Term) -> Term {
+        let strict = self.strategy.is_strict();
+        if self.names.borrow().as_ref().is_none_or(|index| index.strict != strict) {
+            *self.names.borrow_mut() = Some(self.index_names());
+        }
+        let index = self.names.borrow();
+        let expanded = self.expand_globals(term, &mut HashMap::new());
+        fold(term, &expanded, &Hashed::of(&expanded), index.as_ref().unwrap())
+    }
+
+    fn index_names(&self) -> NameIndex {
+        let mut names: HashMap<u64, Vec<(Term, String)>> = HashMap::new();
+        for (name, expr) in &self.env {
+            let term = match self.expand(expr) {
+                Ok(expanded) => Term::from_expr(&expanded),
+                Err(_) => continue,
+            };
+            // Folding every occurrence of a free variable only obscures
+            if let Term::Free(_) = term { continue; }
+            let bucket = names.entry(Hashed::of(&term).hash()).or_default();
+            match bucket.iter_mut().find(|(indexed, _)| *indexed == term) {
+                Some((_, other)) => if (!name.is_ascii(), name.len(), name) < (!other.is_ascii(), other.len(), other) {
+                    *other = name.clone();
+                },
+                None => bucket.push((term, name.clone())),
+            }
+        }
+        NameIndex { strict: self.strategy.is_strict(), names }
+    }
+
+    // Bindings are closed, so their terms replace references without any shifting
+    pub(crate) fn expand_globals(&self, term: &Term, definitions: &mut HashMap<String, Term>) -> Term {
+        match term {
+            Term::Global(name) => {
+                if let Some(definition) = definitions.get(name) {
+                    return definition.clone();
+                }
+                let reference = Expr::MetaVariable(Token::new(TokenType::MetaVar, name.clone(), Span::NONE));
+                let definition = match self.expand(&reference) {
+                    Ok(expanded) => Term::from_expr(&expanded),
+                    Err(_) => term.clone(),
+                };
+                definitions.insert(name.clone(), definition.clone());
+                definition
+            }
+            Term::Abstraction(hint, body) => Term::Abstraction(hint.clone(), Box::new(self.expand_globals(body, definitions))),
+            Term::Application(left, right) => Term::Application(
+                Box::new(self.expand_globals(left, definitions)),
+                Box::new(self.expand_globals(right, definitions)),
+            ),
+            _ => term.clone(),
+        }
+    }
+}
+
+// expanded has the shape of term, except where term refers to a binding, and hashed the shape of expanded
+fn fold(term: &Term, expanded: &Term, hashed: &Hashed, index: &NameIndex) -> Term {
+    if let Term::Global(_) = term {
+        return term.clone();
+    }
+    if let Some(name) = index.get(expanded, hashed) {
+        return Term::Global(name.to_string());
+    }
+    match (term, expanded, hashed) {
+        (Term::Abstraction(hint, body), Term::Abstraction(_, expanded), Hashed::Abstraction(_, hashed)) =>
+            Term::Abstraction(hint.clone(), Box::new(fold(body, expanded, hashed, index))),
+        (Term::Application(left, right), Term::Application(expanded_left, expanded_right), Hashed::Application(_, hashed_left, hashed_right)) =>
+            Term::Application(
+                Box::new(fold(left, expanded_left, hashed_left, index)),
+                Box::new(fold(right, expanded_right, hashed_right, index)),
+            ),
+        _ => term.clone(),
+    }
+}
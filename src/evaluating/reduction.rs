@@ -0,0 +1,426 @@
+use std::cmp::max;
+use std::collections::HashMap;
+
+use crate::evaluating::{Evaluator, Limit, ReductionStrategy, Term};
+use crate::parsing::{Direction, Expr};
+
+// The reduct, and the contracted redex whose path is reversed, as it is built on the way out
+type Contraction = Option<(Term, Redex)>;
+
+// Called before every step with the step's number, the current term
+// and the redex about to be contracted in it
+pub type Observer<'a> = dyn FnMut(usize, &Expr, &Redex) + 'a;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum RedexKind {
+    // (\x.E) N -> E[x->N]
+    Beta,
+    // \x.E x -> E, if x is not free in E
+    Eta,
+    // Unfolding an applied metavariable to its binding
+    Delta,
+}
+
+#[derive(Debug, Clone)]
+pub struct Redex {
+    pub kind: RedexKind,
+    // Position of the redex in the term it is contracted in
+    pub path: Vec<Direction>,
+}
+
+// A single reduction step
+#[derive(Debug, Clone)]
+pub struct Step {
+    // The term after contracting the redex
+    pub expr: Expr,
+    pub redex: Redex,
+}
+
+impl Evaluator {
+    /* Contracts the next redex according to the current strategy,
+    None if the expression is already in the strategy's normal form.
+    Sharing introduced by call by need only lasts for a single step here,
+    use normalize_with to observe a whole reduction.
+    */
+    pub fn step(&mut self, expr: &Expr) -> Option<Step> {
+        self.heap.clear();
+        let term = Term::from_expr(&self.desugar(expr));
+        self.contract_next(&term).map(|(term, redex)| Step {
+            expr: self.read_back(&term).to_expr(),
+            redex,
+        })
+    }
+
+    pub(crate) fn contract_next(&mut self, term: &Term) -> Option<(Term, Redex)> {
+        let contraction = match self.strategy {
+            ReductionStrategy::NormalOrder => self.step_normal_order(term),
+            ReductionStrategy::ApplicativeOrder => self.step_applicative_order(term),
+            ReductionStrategy::CallByName => self.step_call_by_name(term),
+            ReductionStrategy::CallByValue => self.step_call_by_value(term),
+            ReductionStrategy::CallByNeed => self.step_call_by_need(term),
+            ReductionStrategy::HeadSpine => self.step_head_spine(term),
+        };
+        contraction.map(|(term, mut redex)| {
+            redex.path.reverse();
+            (term, redex)
+        })
+    }
+}
+
+// (\.E) N -> E[0->N]
+fn contract(body: &Term, arg: &Term) -> Contraction {
+    Some((Term::instantiate(body, arg), Redex { kind: RedexKind::Beta, path: Vec::new() }))
+}
+
+// Name N -> E N, where Name = E
+fn contract_delta(definition: Term, arg: &Term) -> Contraction {
+    in_function(arg, Some((definition, Redex { kind: RedexKind::Delta, path: Vec::new() })))
+}
+
+// \.E 0 -> E, if 0 is not free in E
+fn contract_eta(body: &Term) -> Contraction {
+    match body {
+        Term::Application(function, arg) if **arg == Term::Var(0) && !function.references(0) =>
+            Some((function.shift(-1, 0), Redex { kind: RedexKind::Eta, path: Vec::new() })),
+        _ => None,
+    }
+}
+
+/* Small step semantics of every strategy.
+Each step function contracts exactly one redex, or returns None if the term
+is already in the normal form the strategy reduces to.
+Metavariables are only unfolded once applied, as the function of a delta redex,
+which every strategy treats like a beta redex in the same position.
+With eta enabled, strategies that reduce under abstractions also contract eta redexes,
+outermost first like beta redexes, except for applicative order which goes innermost first.
+Weak strategies never look inside abstractions, so are unaffected.
+*/
+impl Evaluator {
+    fn step_normal_order(&mut self, term: &Term) -> Contraction {
+        match term {
+            Term::Abstraction(name, body) => self.step_eta(body)
+                .or_else(|| in_body(name, self.step_normal_order(body))),
+            Term::Application(left, right) => {
+                match &**left {
+                    Term::Abstraction(_, body) => return contract(body, right),
+                    Term::Global(name) => return self.step_delta(name, right),
+                    _ => {}
+                }
+                in_function(right, self.step_normal_order(left))
+                    .or_else(|| in_argument(left, self.step_normal_order(right)))
+            }
+            _ => None,
+        }
+    }
+
+    fn step_applicative_order(&mut self, term: &Term) -> Contraction {
+        match term {
+            Term::Abstraction(name, body) => in_body(name, self.step_applicative_order(body))
+                .or_else(|| self.step_eta(body)),
+            Term::Application(left, right) => in_function(right, self.step_applicative_order(left))
+                .or_else(|| in_argument(left, self.step_applicative_order(right)))
+                .or_else(|| match &**left {
+                    Term::Abstraction(_, body) => contract(body, right),
+                    Term::Global(name) => self.step_delta(name, right),
+                    _ => None,
+                }),
+            _ => None,
+        }
+    }
+
+    fn step_call_by_name(&mut self, term: &Term) -> Contraction {
+        match term {
+            Term::Application(left, right) => {
+                match &**left {
+                    Term::Abstraction(_, body) => return contract(body, right),
+                    Term::Global(name) => return self.step_delta(name, right),
+                    _ => {}
+                }
+                in_function(right, self.step_call_by_name(left))
+            }
+            _ => None,
+        }
+    }
+
+    fn step_call_by_value(&mut self, term: &Term) -> Contraction {
+        match term {
+            Term::Application(left, right) => in_function(right, self.step_call_by_value(left))
+                .or_else(|| in_argument(left, self.step_call_by_value(right)))
+                .or_else(|| match &**left {
+                    Term::Abstraction(_, body) => contract(body, right),
+                    Term::Global(name) => self.step_delta(name, right),
+                    _ => None,
+                }),
+            _ => None,
+        }
+    }
+
+    fn step_head_spine(&mut self, term: &Term) -> Contraction {
+        match term {
+            Term::Abstraction(name, body) => self.step_eta(body)
+                .or_else(|| in_body(name, self.step_head_spine(body))),
+            Term::Application(left, right) => in_function(right, self.step_head_spine(left))
+                .or_else(|| match &**left {
+                    Term::Abstraction(_, body) => contract(body, right),
+                    Term::Global(name) => self.step_delta(name, right),
+                    _ => None,
+                }),
+            _ => None,
+        }
+    }
+
+    /* Call by need is call by name with sharing.
+    Non trivial arguments are allocated on the heap and substituted by reference,
+    a reference in head position forces its thunk, which is updated in place,
+    so every other occurrence sees the reduced value.
+    Reduction never happens under abstractions, so thunks are always closed terms.
+    A thunk may reduce to a reference to another thunk, such chains are followed to the value.
+    */
+    fn step_call_by_need(&mut self, term: &Term) -> Contraction {
+        match term {
+            Term::Thunk(i) => {
+                let i = self.dereference(*i);
+                let thunk = self.heap[i].clone();
+                if let Term::Abstraction(_, _) = thunk {
+                    return None;
+                }
+                let (value, redex) = self.step_call_by_need(&thunk)?;
+                self.heap[i] = value;
+                Some((term.clone(), redex))
+            }
+            Term::Application(left, right) => {
+                let function = match &**left {
+                    Term::Thunk(i) => &self.heap[self.dereference(*i)],
+                    left => left,
+                };
+                if let Term::Abstraction(_, body) = function {
+                    let body = (**body).clone();
+                    let arg = match &**right {
+                        Term::Var(_) | Term::Free(_) | Term::Global(_) | Term::Thunk(_) | Term::Abstraction(_, _) => (**right).clone(),
+                        _ => self.allocate((**right).clone()),
+                    };
+                    return contract(&body, &arg);
+                }
+                if let Term::Global(name) = function {
+                    return self.step_delta(&name.clone(), right);
+                }
+                in_function(right, self.step_call_by_need(left))
+            }
+            _ => None,
+        }
+    }
+
+    // Unbound names are stuck, expressions are checked for them before reduction
+    fn step_delta(&self, name: &str, arg: &Term) -> Contraction {
+        let definition = self.env.get(name)?;
+        contract_delta(Term::from_expr(&self.desugar(definition)), arg)
+    }
+
+    fn step_eta(&self, body: &Term) -> Contraction {
+        if self.eta { contract_eta(body) } else { None }
+    }
+
+    fn allocate(&mut self, thunk: Term) -> Term {
+        self.heap.push(thunk);
+        Term::Thunk(self.heap.len() - 1)
+    }
+
+    // The last thunk of a chain of thunks referring to one another.
+    // Arguments that already are references are never allocated again, so chains cannot cycle
+    fn dereference(&self, mut i: usize) -> usize {
+        while let Term::Thunk(j) = self.heap[i] {
+            i = j;
+        }
+        i
+    }
+}
+
+// Thunks and limits
+impl Evaluator {
+    // Replaces thunk references by the values of their thunks
+    pub(crate) fn read_back(&self, term: &Term) -> Term {
+        let mut values = HashMap::new();
+        self.read_back_with(term, &mut values)
+    }
+
+    fn read_back_with(&self, term: &Term, values: &mut HashMap<usize, Term>) -> Term {
+        term.replace_thunks(&mut |i| {
+            if let Some(value) = values.get(&i) {
+                return value.clone();
+            }
+            let value = self.read_back_with(&self.heap[i], values);
+            values.insert(i, value.clone());
+            value
+        })
+    }
+
+    /* The limit the term, as it would be read back, exceeds: nested deeper than max_depth
+    or made of more than max_size nodes. Stops walking as soon as either is exceeded,
+    so this is safe to call on any term. Thunks are shared, so their measures are only
+    computed once where they are exact, a measure cut off at a deep position
+    may be within the limits at a shallower one.
+    */
+    pub(crate) fn exceeded_limit(&self, term: &Term) -> Option<Limit> {
+        let (depth, size) = self.measure(term, self.max_depth, self.max_size, &mut HashMap::new());
+        if depth > self.max_depth {
+            Some(Limit::Depth(self.max_depth))
+        } else if size > self.max_size {
+            Some(Limit::Size(self.max_size))
+        } else {
+            None
+        }
+    }
+
+    // Exact depth and size if both are within their limits, otherwise one of them exceeds its limit
+    fn measure(&self, term: &Term, depth_limit: usize, size_limit: usize, thunks: &mut HashMap<usize, (usize, usize)>) -> (usize, usize) {
+        if depth_limit == 0 || size_limit == 0 { return (1, 1); }
+        match term {
+            Term::Thunk(i) => {
+                if let Some(measure) = thunks.get(i) { return *measure; }
+                let (depth, size) = self.measure(&self.heap[*i], depth_limit, size_limit, thunks);
+                if depth <= depth_limit && size <= size_limit { thunks.insert(*i, (depth, size)); }
+                (depth, size)
+            }
+            Term::Abstraction(_, body) => {
+                let (depth, size) = self.measure(body, depth_limit - 1, size_limit - 1, thunks);
+                (1 + depth, 1 + size)
+            }
+            Term::Application(left, right) => {
+                let (left_depth, left_size) = self.measure(left, depth_limit - 1, size_limit - 1, thunks);
+                if left_depth >= depth_limit || left_size >= size_limit { return (1 + left_depth, 1 + left_size); }
+                let (right_depth, right_size) = self.measure(right, depth_limit - 1, size_limit - 1 - left_size, thunks);
+                (1 + max(left_depth, right_depth), 1 + left_size + right_size)
+            }
+            Term::Var(_) | Term::Free(_) | Term::Global(_) => (1, 1),
+        }
+    }
+}
+
+fn in_body(name: &str, contraction: Contraction) -> Contraction {
+    contraction.map(|(body, mut redex)| {
+        redex.path.push(Direction::Body);
+        (Term::Abstraction(name.to_string(), Box::new(body)), redex)
+    })
+}
+
+fn in_function(right: &Term, contraction: Contraction) -> Contraction {
+    contraction.map(|(left, mut redex)| {
+        redex.path.push(Direction::Function);
+        (Term::Application(Box::new(left), Box::new(right.clone())), redex)
+    })
+}
+
+fn in_argument(left: &Term, contraction: Contraction) -> Contraction {
+    contraction.map(|(right, mut redex)| {
+        redex.path.push(Direction::Argument);
+        (Term::Application(Box::new(left.clone()), Box::new(right)), redex)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force_evaluate;
+
+    const NORMAL: ReductionStrategy = ReductionStrategy::NormalOrder;
+    const APPLICATIVE: ReductionStrategy = ReductionStrategy::ApplicativeOrder;
+    const NAME: ReductionStrategy = ReductionStrategy::CallByName;
+    const VALUE: ReductionStrategy = ReductionStrategy::CallByValue;
+    const NEED: ReductionStrategy = ReductionStrategy::CallByNeed;
+    const HEAD: ReductionStrategy = ReductionStrategy::HeadSpine;
+
+    fn term(xs: &str) -> Term {
+        Term::from_expr(&force_evaluate(xs))
+    }
+
+    fn reduce(strategy: ReductionStrategy, xs: &str) -> (Term, usize, Option<Limit>) {
+        let mut evaluator = Evaluator::with_strategy(strategy);
+        let term = Term::from_expr(&evaluator.desugar(&force_evaluate(xs)));
+        evaluator.reduce(term, None)
+    }
+
+    fn assert_reduces(strategies: &[ReductionStrategy], xs: &str, expected: &str) {
+        for strategy in strategies {
+            let (term, _, limit) = reduce(*strategy, xs);
+            assert_eq!(limit, None, "{} under {:?}", xs, strategy);
+            assert_eq!(term, self::term(expected), "{} under {:?}", xs, strategy);
+        }
+    }
+
+    #[test]
+    fn every_strategy_contracts_an_applied_abstraction() {
+        assert_reduces(&ReductionStrategy::all(), r"(\x.x) y", "y");
+        assert_reduces(&ReductionStrategy::all(), r"(\x.\y.x) a b", "a");
+    }
+
+    #[test]
+    fn every_strategy_unfolds_applied_bindings() {
+        assert_reduces(&ReductionStrategy::all(), "K a b", "a");
+        // Unapplied references stay as they are
+        assert_reduces(&ReductionStrategy::all(), "x K", "x K");
+    }
+
+    #[test]
+    fn only_strong_strategies_reduce_under_abstractions() {
+        assert_reduces(&[NORMAL, APPLICATIVE, HEAD], r"\x.(\y.y) x", r"\x.x");
+        assert_reduces(&[NAME, VALUE, NEED], r"\x.(\y.y) x", r"\x.(\y.y) x");
+    }
+
+    #[test]
+    fn only_some_strategies_reduce_arguments_of_stuck_applications() {
+        assert_reduces(&[NORMAL, APPLICATIVE, VALUE], r"x ((\y.y) z)", "x z");
+        assert_reduces(&[NAME, NEED, HEAD], r"x ((\y.y) z)", r"x ((\y.y) z)");
+    }
+
+    #[test]
+    fn strategies_reducing_arguments_first_diverge_on_discarded_arguments() {
+        let discarding = r"(\x.\y.x) a ((\x.x x) (\x.x x))";
+        assert_reduces(&[NORMAL, NAME, NEED, HEAD], discarding, "a");
+        for strategy in [APPLICATIVE, VALUE] {
+            let (_, _, limit) = reduce(strategy, discarding);
+            assert_eq!(limit, Some(Limit::Steps(Evaluator::DEFAULT_FUEL)), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn strong_strategies_reach_the_normal_form_of_arithmetic() {
+        assert_reduces(&[NORMAL, APPLICATIVE], "PLUS 1 2", "3");
+        assert_reduces(&[NORMAL, APPLICATIVE], "MULT 2 3", "6");
+        assert_reduces(&[NORMAL, APPLICATIVE], "NOT TRUE", r"\x.\y.y");
+    }
+
+    #[test]
+    fn call_by_need_shares_arguments() {
+        let duplicating = r"(\x.x x) ((\y.y) (\z.z))";
+        assert_reduces(&[NAME, NEED], duplicating, r"\z.z");
+        let (_, by_name, _) = reduce(NAME, duplicating);
+        let (_, by_need, _) = reduce(NEED, duplicating);
+        assert_eq!((by_name, by_need), (4, 3));
+    }
+
+    #[test]
+    fn eta_redexes_are_contracted_if_enabled() {
+        let mut evaluator = Evaluator::new();
+        evaluator.eta = true;
+        let (reduct, _, _) = evaluator.reduce(term(r"\x.f x"), None);
+        assert_eq!(reduct, term("f"));
+        let (reduct, _, _) = evaluator.reduce(term(r"\x.x x"), None);
+        assert_eq!(reduct, term(r"\x.x x"));
+    }
+
+    #[test]
+    fn growing_terms_hit_the_size_limit() {
+        let mut evaluator = Evaluator::new();
+        evaluator.max_size = 100;
+        let (_, _, limit) = evaluator.reduce(term(r"(\x.x x x) (\x.x x x)"), None);
+        assert_eq!(limit, Some(Limit::Size(100)));
+    }
+
+    #[test]
+    fn deep_terms_hit_the_depth_limit() {
+        let mut evaluator = Evaluator::new();
+        evaluator.max_depth = 10;
+        let (_, steps, limit) = evaluator.reduce(term(r"\a.\b.\c.\d.\e.\f.\g.\h.\i.\j.\k.k"), None);
+        assert_eq!((steps, limit), (0, Some(Limit::Depth(10))));
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt::{Display, Error, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub enum ReductionStrategy {
+    // Leftmost outermost redex first, reduces under abstractions
+    #[default]
+    NormalOrder,
+    // Leftmost innermost redex first, arguments are reduced before substitution
+    ApplicativeOrder,
+    // Leftmost outermost, but never under abstractions or inside arguments
+    CallByName,
+    // Arguments are reduced before substitution, but never under abstractions
+    CallByValue,
+    // Call by name where each argument is reduced at most once and the result shared
+    CallByNeed,
+    // Reduces the head of the spine, including under the operator's abstractions
+    HeadSpine,
+}
+
+impl ReductionStrategy {
+    pub fn all() -> [ReductionStrategy; 6] {
+        [
+            ReductionStrategy::NormalOrder,
+            ReductionStrategy::ApplicativeOrder,
+            ReductionStrategy::CallByName,
+            ReductionStrategy::CallByValue,
+            ReductionStrategy::CallByNeed,
+            ReductionStrategy::HeadSpine,
+        ]
+    }
+
+    // Whether arguments are reduced before they are substituted
+    pub fn is_strict(self) -> bool {
+        matches!(self, ReductionStrategy::ApplicativeOrder | ReductionStrategy::CallByValue)
+    }
+
+    // The form a term is left in once the strategy has no redex left to contract
+    pub fn normal_form(self) -> &'static str {
+        match self {
+            ReductionStrategy::NormalOrder | ReductionStrategy::ApplicativeOrder => "normal form",
+            ReductionStrategy::CallByName | ReductionStrategy::CallByNeed => "weak head normal form",
+            ReductionStrategy::CallByValue => "weak normal form",
+            ReductionStrategy::HeadSpine => "head normal form",
+        }
+    }
+}
+
+impl Display for ReductionStrategy {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        let name = match self {
+            ReductionStrategy::NormalOrder => "normal",
+            ReductionStrategy::ApplicativeOrder => "applicative",
+            ReductionStrategy::CallByName => "cbn",
+            ReductionStrategy::CallByValue => "cbv",
+            ReductionStrategy::CallByNeed => "need",
+            ReductionStrategy::HeadSpine => "head",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ReductionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ReductionStrategy, String> {
+        match s {
+            "normal" | "nor" | "no" => Ok(ReductionStrategy::NormalOrder),
+            "applicative" | "aor" | "ao" => Ok(ReductionStrategy::ApplicativeOrder),
+            "cbn" | "name" => Ok(ReductionStrategy::CallByName),
+            "cbv" | "value" => Ok(ReductionStrategy::CallByValue),
+            "need" | "cbneed" | "lazy" => Ok(ReductionStrategy::CallByNeed),
+            "head" | "headspine" | "hs" => Ok(ReductionStrategy::HeadSpine),
+            _ => Err(format!("Unknown reduction strategy: {}", s)),
+        }
+    }
+}
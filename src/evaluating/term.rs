@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::mem::discriminant;
+
+use crate::lexing::{Span, Token, TokenType};
+use crate::parsing::Expr;
+
+/* Nameless representation used for reduction.
+Bound variables are de Bruijn indices counting the abstractions between the
+variable and its binder, so \x.\y.x is \.\.1 and alpha-equivalent terms are equal.
+Abstractions keep the name they were written with, only as a hint for naming
+them again when converting back to an expression.
+*/
+#[derive(Debug, Clone)]
+pub enum Term {
+    Var(usize),
+    // A variable no abstraction in the term binds
+    Free(String),
+    // Reference to a binding of the environment, only unfolded once it is applied
+    Global(String),
+    Abstraction(String, Box<Term>),
+    Application(Box<Term>, Box<Term>),
+    // Reference to a shared call by need thunk, these never escape a reduction
+    Thunk(usize),
+}
+
+// Equality is alpha-equivalence, names of abstractions are ignored
+impl PartialEq for Term {
+    fn eq(&self, other: &Term) -> bool {
+        match (self, other) {
+            (Term::Var(i), Term::Var(j)) => i == j,
+            (Term::Free(x), Term::Free(y)) | (Term::Global(x), Term::Global(y)) => x == y,
+            (Term::Abstraction(_, x), Term::Abstraction(_, y)) => x == y,
+            (Term::Application(f, x), Term::Application(g, y)) => f == g && x == y,
+            (Term::Thunk(i), Term::Thunk(j)) => i == j,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Term {}
+
+impl Hash for Term {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+        match self {
+            Term::Var(i) | Term::Thunk(i) => i.hash(state),
+            Term::Free(name) | Term::Global(name) => name.hash(state),
+            Term::Abstraction(_, body) => body.hash(state),
+            Term::Application(left, right) => {
+                left.hash(state);
+                right.hash(state);
+            }
+        }
+    }
+}
+
+// Conversion from and to named expressions
+impl Term {
+    // Expects a desugared expression, without bindings or lets, metavariables become references to the environment
+    pub fn from_expr(expr: &Expr) -> Term {
+        Term::from_expr_in(expr, &mut Vec::new())
+    }
+
+    // binders holds the names of the enclosing abstractions, innermost last
+    fn from_expr_in(expr: &Expr, binders: &mut Vec<String>) -> Term {
+        match expr {
+            Expr::Variable(name, _) => match binders.iter().rev().position(|binder| binder == name) {
+                Some(index) => Term::Var(index),
+                None => Term::Free(name.clone()),
+            },
+            Expr::Abstraction(name, body, _) => {
+                binders.push(name.clone());
+                let body = Term::from_expr_in(body, binders);
+                binders.pop();
+                Term::Abstraction(name.clone(), Box::new(body))
+            }
+            Expr::Application(left, right, _) => Term::Application(
+                Box::new(Term::from_expr_in(left, binders)),
+                Box::new(Term::from_expr_in(right, binders)),
+            ),
+            Expr::Grouping(expr, _) => Term::from_expr_in(expr, binders),
+            Expr::MetaVariable(token) => Term::Global(token.lexeme.clone()),
+            Expr::Binding(_, _, _) | Expr::Let(_, _, _, _) | Expr::LetRec(_, _, _, _) => unreachable!("Converting binding or let to term, expressions are desugared first")
+        }
+    }
+
+    /* Names every abstraction after its hint, priming it until it neither captures
+    a free variable of its body nor shadows an enclosing abstraction.
+    Terms keep no positions, so no node of the result has a span.
+    */
+    pub fn to_expr(&self) -> Expr {
+        self.to_expr_in(&mut Vec::new())
+    }
+
+    fn to_expr_in(&self, binders: &mut Vec<String>) -> Expr {
+        match self {
+            Term::Var(index) => Expr::Variable(binders[binders.len() - 1 - index].clone(), Span::NONE),
+            Term::Free(name) => Expr::Variable(name.clone(), Span::NONE),
+            Term::Global(name) => Expr::MetaVariable(Token::new(TokenType::MetaVar, name.clone(), Span::NONE)),
+            Term::Abstraction(hint, body) => {
+                let mut taken: HashSet<String> = binders.iter().cloned().collect();
+                body.free_names(&mut taken);
+                let mut name = hint.clone();
+                while taken.contains(&name) {
+                    name.push('\'');
+                }
+                binders.push(name.clone());
+                let body = body.to_expr_in(binders);
+                binders.pop();
+                Expr::Abstraction(name, Box::new(body), Span::NONE)
+            }
+            Term::Application(left, right) => Expr::Application(
+                Box::new(left.to_expr_in(binders)),
+                Box::new(right.to_expr_in(binders)),
+                Span::NONE,
+            ),
+            Term::Thunk(i) => Expr::Variable(format!("#{}", i), Span::NONE),
+        }
+    }
+
+    fn free_names(&self, acc: &mut HashSet<String>) {
+        match self {
+            Term::Free(name) => {
+                acc.insert(name.clone());
+            }
+            Term::Abstraction(_, body) => body.free_names(acc),
+            Term::Application(left, right) => {
+                left.free_names(acc);
+                right.free_names(acc);
+            }
+            Term::Var(_) | Term::Global(_) | Term::Thunk(_) => {}
+        }
+    }
+}
+
+// Substitution
+impl Term {
+    // (\.E) N -> E[0->N]
+    pub fn instantiate(body: &Term, arg: &Term) -> Term {
+        body.instantiate_at(0, arg)
+    }
+
+    /* Replaces the variable bound by the abstraction depth levels up, which is being removed.
+    Indices escaping that abstraction shift down, and arg is shifted by the number of
+    abstractions it is moved under, so none of its variables are captured.
+    */
+    fn instantiate_at(&self, depth: usize, arg: &Term) -> Term {
+        match self {
+            Term::Var(index) => {
+                if *index == depth {
+                    arg.shift(depth as isize, 0)
+                } else if *index > depth {
+                    Term::Var(index - 1)
+                } else {
+                    self.clone()
+                }
+            }
+            Term::Abstraction(name, body) => Term::Abstraction(name.clone(), Box::new(body.instantiate_at(depth + 1, arg))),
+            Term::Application(left, right) => Term::Application(
+                Box::new(left.instantiate_at(depth, arg)),
+                Box::new(right.instantiate_at(depth, arg)),
+            ),
+            Term::Free(_) | Term::Global(_) | Term::Thunk(_) => self.clone(),
+        }
+    }
+
+    // Adds d to every index that escapes the innermost cutoff abstractions
+    pub fn shift(&self, d: isize, cutoff: usize) -> Term {
+        if d == 0 { return self.clone(); }
+        match self {
+            Term::Var(index) => if *index >= cutoff {
+                Term::Var((*index as isize + d) as usize)
+            } else {
+                self.clone()
+            },
+            Term::Abstraction(name, body) => Term::Abstraction(name.clone(), Box::new(body.shift(d, cutoff + 1))),
+            Term::Application(left, right) => Term::Application(
+                Box::new(left.shift(d, cutoff)),
+                Box::new(right.shift(d, cutoff)),
+            ),
+            Term::Free(_) | Term::Global(_) | Term::Thunk(_) => self.clone(),
+        }
+    }
+
+    // Whether the variable bound index abstractions up occurs in the term
+    pub fn references(&self, index: usize) -> bool {
+        match self {
+            Term::Var(i) => *i == index,
+            Term::Abstraction(_, body) => body.references(index + 1),
+            Term::Application(left, right) => left.references(index) || right.references(index),
+            Term::Free(_) | Term::Global(_) | Term::Thunk(_) => false,
+        }
+    }
+
+    // \x.E x, where x is not free in E
+    pub fn eta_expand(&self) -> Term {
+        Term::Abstraction(
+            "x".to_string(),
+            Box::new(Term::Application(Box::new(self.shift(1, 0)), Box::new(Term::Var(0)))),
+        )
+    }
+
+    // Replaces every thunk reference with the result of f
+    pub(crate) fn replace_thunks(&self, f: &mut dyn FnMut(usize) -> Term) -> Term {
+        match self {
+            Term::Thunk(i) => f(*i),
+            Term::Abstraction(name, body) => Term::Abstraction(name.clone(), Box::new(body.replace_thunks(f))),
+            Term::Application(left, right) => Term::Application(
+                Box::new(left.replace_thunks(f)),
+                Box::new(right.replace_thunks(f)),
+            ),
+            Term::Var(_) | Term::Free(_) | Term::Global(_) => self.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::force_evaluate;
+
+    fn term(xs: &str) -> Term {
+        Term::from_expr(&force_evaluate(xs))
+    }
+
+    fn abstraction(body: Term) -> Term {
+        Term::Abstraction("x".to_string(), Box::new(body))
+    }
+
+    fn application(left: Term, right: Term) -> Term {
+        Term::Application(Box::new(left), Box::new(right))
+    }
+
+    #[test]
+    fn variables_are_indexed_by_their_binders() {
+        assert_eq!(term(r"\x.\y.x"), abstraction(abstraction(Term::Var(1))));
+        assert_eq!(term(r"\x.\x.x"), abstraction(abstraction(Term::Var(0))));
+        assert_eq!(term(r"\x.y x"), abstraction(application(Term::Free("y".to_string()), Term::Var(0))));
+        assert_eq!(term("K"), Term::Global("K".to_string()));
+    }
+
+    #[test]
+    fn equality_is_alpha_equivalence() {
+        assert_eq!(term(r"\x.\y.x y"), term(r"\a.\b.a b"));
+        assert_ne!(term(r"\x.\y.x"), term(r"\x.\y.y"));
+        assert_ne!(term(r"\x.y"), term(r"\x.z"));
+    }
+
+    #[test]
+    fn shift_only_moves_indices_escaping_the_cutoff() {
+        assert_eq!(Term::Var(0).shift(1, 0), Term::Var(1));
+        assert_eq!(Term::Var(0).shift(1, 1), Term::Var(0));
+        // \.0 1 -> \.0 3
+        let escaping = abstraction(application(Term::Var(0), Term::Var(1)));
+        assert_eq!(escaping.shift(2, 0), abstraction(application(Term::Var(0), Term::Var(3))));
+        assert_eq!(Term::Var(3).shift(-1, 0), Term::Var(2));
+    }
+
+    #[test]
+    fn instantiate_substitutes_the_removed_binder() {
+        // (\.0 0) a -> a a
+        let free = Term::Free("a".to_string());
+        assert_eq!(Term::instantiate(&application(Term::Var(0), Term::Var(0)), &free), application(free.clone(), free.clone()));
+        // Indices bound outside the removed abstraction move down
+        assert_eq!(Term::instantiate(&Term::Var(1), &free), Term::Var(0));
+    }
+
+    #[test]
+    fn instantiate_avoids_capture() {
+        // \z.(\x.\y.x) z -> \z.\y.z, the argument is shifted under \y so it still refers to \z
+        let body = abstraction(Term::Var(1));
+        assert_eq!(Term::instantiate(&body, &Term::Var(0)), abstraction(Term::Var(1)));
+        assert_eq!(abstraction(Term::instantiate(&body, &Term::Var(0))), term(r"\z.\y.z"));
+        // (\x.\y.x y) y -> \y'.y y', a free y is not captured either
+        let body = match term(r"\x.\y.x y") {
+            Term::Abstraction(_, body) => *body,
+            _ => unreachable!(),
+        };
+        let reduct = Term::instantiate(&body, &Term::Free("y".to_string()));
+        assert_eq!(reduct, term(r"\v.y v"));
+        assert_eq!(reduct.to_expr().to_string(), r"\y'.y y'");
+    }
+
+    #[test]
+    fn to_expr_renames_binders_that_would_capture() {
+        // \x.\x.x referring to the outer x, and \y.y with y free in the body
+        let shadowing = Term::Abstraction("x".to_string(), Box::new(Term::Abstraction("x".to_string(), Box::new(Term::Var(1)))));
+        assert_eq!(shadowing.to_expr().to_string(), r"\x.\x'.x");
+        let capturing = Term::Abstraction("y".to_string(), Box::new(Term::Free("y".to_string())));
+        assert_eq!(capturing.to_expr().to_string(), r"\y'.y");
+        assert_eq!(Term::from_expr(&shadowing.to_expr()), shadowing);
+    }
+
+    #[test]
+    fn references_looks_through_abstractions() {
+        assert!(abstraction(Term::Var(1)).references(0));
+        assert!(!abstraction(Term::Var(0)).references(0));
+        assert!(!Term::Free("x".to_string()).references(0));
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt::{Display, Error, Formatter};
+
+use crate::lexing::Span;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum LexError {
+    // A character that does not start any token
+    UnexpectedCharacter { character: char, span: Span },
+    // A {- without its matching -}, the span is the opening delimiter
+    UnterminatedComment { span: Span },
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedCharacter { span, .. } | LexError::UnterminatedComment { span } => *span,
+        }
+    }
+
+    // A suggestion of how to fix the error
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            LexError::UnexpectedCharacter { character: '<', .. } => Some("did you mean the binding operator `<-`?".to_string()),
+            LexError::UnexpectedCharacter { character: '{', .. } => Some("did you mean a block comment `{- ... -}`?".to_string()),
+            LexError::UnexpectedCharacter { .. } => None,
+            LexError::UnterminatedComment { .. } => Some("close it with `-}`, block comments nest so every `{-` inside needs one too".to_string()),
+        }
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            LexError::UnexpectedCharacter { character, .. } => write!(f, "Unexpected character {}", character),
+            LexError::UnterminatedComment { .. } => write!(f, "Unterminated block comment"),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
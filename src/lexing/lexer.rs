@@ -1,112 +1,271 @@
 use std::iter::Peekable;
-use std::str::Chars;
+use std::str::CharIndices;
 
-use crate::lexing::token::{Token, TokenType};
+use crate::lexing::{Comment, LexError, Span, Token, TokenType};
 
 pub struct Lexer {
     line: i32,
     col: i32,
+    // Byte offset of the character being lexed
+    start: usize,
+    // Open parentheses and brackets, newlines inside them are whitespace
+    depth: usize,
+    // Comments of everything lexed so far, in order
+    comments: Vec<Comment>,
 }
 
 impl Lexer {
+    // Lines and columns are counted from 1
     pub fn new() -> Lexer {
         Lexer {
-            line: 0,
-            col: 0,
+            line: 1,
+            col: 1,
+            start: 0,
+            depth: 0,
+            comments: Vec::new(),
         }
     }
+}
 
-    pub fn lex(&mut self, xs: &str) -> Result<Vec<Token>, Vec<String>> {
-        let mut it = xs.chars().peekable();
+impl Default for Lexer {
+    fn default() -> Lexer {
+        Lexer::new()
+    }
+}
+
+impl Lexer {
+
+    // Comments are skipped like whitespace, but kept here, e.g. to be written back out
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    pub fn lex(&mut self, xs: &str) -> Result<Vec<Token>, Vec<LexError>> {
+        let (tokens, errors) = self.lex_recovering(xs);
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Skips unexpected characters, returning the tokens around them along with the errors
+    pub fn lex_recovering(&mut self, xs: &str) -> (Vec<Token>, Vec<LexError>) {
+        let mut it = xs.char_indices().peekable();
         let mut tokens = Vec::<Token>::new();
-        let mut errors = Vec::<String>::new();
+        let mut errors = Vec::<LexError>::new();
 
-        while let Some(c) = it.next() {
-            // Just allowing single character identifiers currently
+        while let Some((start, c)) = it.next() {
+            self.start = start;
             match c {
-                '(' => tokens.push(self.create_token(TokenType::LParen, char::to_string(&c))),
-                ')' => tokens.push(self.create_token(TokenType::RParen, char::to_string(&c))),
-                '.' => tokens.push(self.create_token(TokenType::Dot, char::to_string(&c))),
-                '\\' => tokens.push(self.create_token(TokenType::Lambda, char::to_string(&c))),
-                ' ' => tokens.push(self.create_token(TokenType::Space, char::to_string(&c))),
+                '(' => {
+                    self.depth += 1;
+                    tokens.push(self.create_token(TokenType::LParen, char::to_string(&c)))
+                }
+                ')' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    tokens.push(self.create_token(TokenType::RParen, char::to_string(&c)))
+                }
+                '[' => {
+                    self.depth += 1;
+                    tokens.push(self.create_token(TokenType::LBracket, char::to_string(&c)))
+                }
+                ']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    tokens.push(self.create_token(TokenType::RBracket, char::to_string(&c)))
+                }
+                ',' => tokens.push(self.create_token(TokenType::Comma, char::to_string(&c))),
+                // An arrow may separate the binders of an abstraction from its body, like a dot
+                '.' | '→' => tokens.push(self.create_token(TokenType::Dot, char::to_string(&c))),
+                '\\' | 'λ' => tokens.push(self.create_token(TokenType::Lambda, char::to_string(&c))),
+                // Whitespace only separates tokens
+                ' ' | '\t' | '\r' => {}
                 ';' => tokens.push(self.create_token(TokenType::Semicolon, char::to_string(&c))),
-                '=' => tokens.push(self.create_token(TokenType::Equal, char::to_string(&c))),
-                '<' => {
-                    if let Some(curr) = it.next() {
-                        if curr == '-' {
-                            tokens.push(self.create_token(TokenType::LeftArrow, "<-".to_string()))
-                        } else {
-                            errors.push(self.format_error(format!("Unexpected character: <{}", curr)))
+                '=' | '≡' => tokens.push(self.create_token(TokenType::Equal, char::to_string(&c))),
+                '-' => match it.peek() {
+                    Some((_, '>')) => {
+                        it.next();
+                        tokens.push(self.create_token(TokenType::Dot, "->".to_string()));
+                        self.col += 1;
+                    }
+                    Some((_, '-')) => self.lex_line_comment(&mut it, c),
+                    _ => errors.push(self.unexpected_character(c)),
+                },
+                '#' => self.lex_line_comment(&mut it, c),
+                '{' => {
+                    if let Some((_, '-')) = it.peek() {
+                        if let Err(error) = self.lex_block_comment(&mut it, c) {
+                            errors.push(error);
                         }
+                    } else {
+                        errors.push(self.unexpected_character(c))
                     }
                 }
-                'a'...'z' => tokens.push(
-                    self.create_token(
-                        TokenType::Var, char::to_string(&c),
-                    )
-                ),
-                '$' | 'A'...'Z' => {
-                    match self.parse_metavariable(&mut it, c) {
-                        Ok(x) => tokens.push(x),
-                        Err(err) => errors.push(err)
-                    };
-                    continue;
+                ':' => {
+                    if let Some((_, '=')) = it.peek() {
+                        it.next();
+                        tokens.push(self.create_token(TokenType::Equal, ":=".to_string()));
+                        self.col += 1;
+                    } else {
+                        errors.push(self.unexpected_character(c))
+                    }
+                }
+                '<' => {
+                    if let Some((_, '-')) = it.peek() {
+                        it.next();
+                        tokens.push(self.create_token(TokenType::LeftArrow, "<-".to_string()));
+                        self.col += 1;
+                    } else {
+                        errors.push(self.unexpected_character(c))
+                    }
                 }
+                '0'..='9' => tokens.push(self.lex_number(&mut it, c)),
+                // Upper case letters of any script start metavariables, other letters variables
+                '$' => tokens.push(self.lex_identifier(&mut it, c, TokenType::MetaVar)),
+                c if c.is_uppercase() => tokens.push(self.lex_identifier(&mut it, c, TokenType::MetaVar)),
+                c if Lexer::is_id_start(c) => tokens.push(self.lex_identifier(&mut it, c, TokenType::Var)),
                 '\n' => {
+                    // Ends a statement unless inside parentheses or brackets. A binding at the start
+                    // of the next line means one was left unclosed, which must not swallow the rest of the input
+                    if self.depth > 0 && Lexer::starts_binding(&xs[start + 1..]) {
+                        self.depth = 0;
+                    }
+                    if self.depth == 0 {
+                        tokens.push(self.create_token(TokenType::Newline, char::to_string(&c)));
+                    }
                     self.line += 1;
-                    self.col = 0;
+                    self.col = 1;
+                    continue;
                 }
-                '\r' => self.col = 0,
-                _ => errors.push(self.format_error(format!("Unexpected character {}", c))),
+                _ => errors.push(self.unexpected_character(c)),
             };
             self.col += 1
         }
 
+        self.start = xs.len();
         tokens.push(self.create_token(TokenType::EOF, "".to_string()));
-
-        if errors.is_empty() {
-            Ok(tokens)
-        } else {
-            Err(errors)
-        }
+        (tokens, errors)
     }
 
     fn create_token(&self, ttype: TokenType, lexeme: String) -> Token {
-        Token::new(ttype, lexeme, self.line, self.col)
+        let span = Span::new(self.start, self.start + lexeme.len(), self.line, self.col);
+        Token::new(ttype, lexeme, span)
     }
 
-    fn format_error(&self, message: String) -> String {
-        format!("{}:{}: {}", self.line, self.col, message)
+    fn unexpected_character(&self, character: char) -> LexError {
+        let span = Span::new(self.start, self.start + character.len_utf8(), self.line, self.col);
+        LexError::UnexpectedCharacter { character, span }
     }
 
-    fn parse_metavariable(&mut self, it: &mut Peekable<Chars>, first: char) -> Result<Token, String> {
+    // Identifiers run until the first character that cannot continue them
+    fn lex_identifier(&mut self, it: &mut Peekable<CharIndices>, first: char, ttype: TokenType) -> Token {
         let mut acc = first.to_string();
         let col = self.col;
 
-//        if let Some(c) = it.next() {
-//            if !Lexer::is_id_start(c) { // Could change back to is_id_start. But allow numeric first chars
-//                return Err(format!("Invalid identifier {}", c));
-//            } else {
-//                acc.push(c);
-//                self.col += 1;
-//            }
-//        }
-
-        while let Some(c) = it.peek() {
+        while let Some((_, c)) = it.peek() {
             if !Lexer::is_id_char(*c) { break; }
             acc.push(*c);
             self.col += 1;
             it.next();
         }
 
-        Ok(Token::new(TokenType::MetaVar, acc, self.line, col))
+        let ttype = match acc.as_str() {
+            "let" => TokenType::Let,
+            "letrec" => TokenType::LetRec,
+            "in" => TokenType::In,
+            _ => ttype,
+        };
+        let span = Span::new(self.start, self.start + acc.len(), self.line, col);
+        Token::new(ttype, acc, span)
+    }
+
+    fn lex_number(&mut self, it: &mut Peekable<CharIndices>, first: char) -> Token {
+        let mut acc = first.to_string();
+        let col = self.col;
+
+        while let Some((_, c)) = it.peek() {
+            if !c.is_ascii_digit() { break; }
+            acc.push(*c);
+            self.col += 1;
+            it.next();
+        }
+
+        let span = Span::new(self.start, self.start + acc.len(), self.line, col);
+        Token::new(TokenType::Number, acc, span)
+    }
+
+    // Runs to the end of the line, leaving the newline to end the statement
+    fn lex_line_comment(&mut self, it: &mut Peekable<CharIndices>, first: char) {
+        let mut text = first.to_string();
+        let col = self.col;
+
+        while let Some((_, c)) = it.next_if(|(_, c)| *c != '\n') {
+            text.push(c);
+            self.col += 1;
+        }
+
+        let span = Span::new(self.start, self.start + text.len(), self.line, col);
+        self.comments.push(Comment { text, span });
+    }
+
+    // Runs to the -} matching the {-, they nest so code containing comments can be commented out.
+    // Newlines inside are whitespace, even outside parentheses
+    fn lex_block_comment(&mut self, it: &mut Peekable<CharIndices>, first: char) -> Result<(), LexError> {
+        let mut text = first.to_string();
+        let (line, col) = (self.line, self.col);
+        let mut depth = 0;
+        let mut previous = first;
+
+        for (_, c) in it.by_ref() {
+            text.push(c);
+            if previous == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+            match (previous, c) {
+                ('{', '-') => depth += 1,
+                ('-', '}') => depth -= 1,
+                _ => {
+                    previous = c;
+                    continue;
+                }
+            }
+            if depth == 0 {
+                let span = Span::new(self.start, self.start + text.len(), line, col);
+                self.comments.push(Comment { text, span });
+                return Ok(());
+            }
+            // A delimiter does not start another, e.g. {-} only opens
+            previous = ' ';
+        }
+
+        if previous == '\n' {
+            self.line += 1;
+            self.col = 0;
+        }
+        Err(LexError::UnterminatedComment { span: Span::new(self.start, self.start + 2, line, col) })
+    }
+
+    // λ is a letter, but always lambda
+    // Whether line starts with a binding at its first column, e.g. Name = ...
+    fn starts_binding(line: &str) -> bool {
+        let mut chars = line.chars();
+        match chars.next() {
+            Some(c) if c == '$' || c.is_uppercase() => {}
+            _ => return false,
+        }
+        let rest = chars.as_str().trim_start_matches(Lexer::is_id_char).trim_start_matches([' ', '\t']);
+        ["=", "≡", ":=", "<-"].iter().any(|operator| rest.starts_with(operator))
     }
 
     fn is_id_start(c: char) -> bool {
-        'c' == '_' || c.is_ascii_alphabetic()
+        c == '_' || (c.is_alphabetic() && c != 'λ')
     }
 
+    // Primes allow the conventional x, x', x''
     fn is_id_char(c: char) -> bool {
-        Lexer::is_id_start(c) || c.is_ascii_digit()
+        Lexer::is_id_start(c) || c.is_numeric() || c == '\''
     }
-}
\ No newline at end of file
+}
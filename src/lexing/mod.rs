@@ -1,8 +1,9 @@
+pub use error::LexError;
 pub use lexer::Lexer;
-pub use token::{Token, TokenType};
+pub use span::Span;
+pub use token::{Comment, Token, TokenType};
 
+pub mod error;
 pub mod lexer;
+pub mod span;
 pub mod token;
-
-
-
@@ -0,0 +1,36 @@
+use std::fmt::{Display, Error, Formatter};
+
+// A byte range of the source, with the line and column it starts at
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: i32,
+    pub col: i32,
+}
+
+impl Span {
+    // For tokens and terms that do not come from the source, e.g. the result of a reduction
+    pub const NONE: Span = Span { start: 0, end: 0, line: -1, col: -1 };
+
+    pub fn new(start: usize, end: usize, line: i32, col: i32) -> Span {
+        Span { start, end, line, col }
+    }
+
+    // From the start of this span to the end of other
+    pub fn to(&self, other: Span) -> Span {
+        if self.is_none() { return other; }
+        if other.is_none() { return *self; }
+        Span { end: other.end.max(self.end), ..*self }
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.line < 0
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
@@ -1,24 +1,32 @@
 use std::fmt::{Debug, Display, Error, Formatter};
 
+use crate::lexing::Span;
+
 #[derive(Clone)]
 pub struct Token {
     pub ttype: TokenType,
     pub lexeme: String,
-    pub line: i32,
-    pub col: i32,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(ttype: TokenType, lexeme: String, line: i32, col: i32) -> Token {
+    pub fn new(ttype: TokenType, lexeme: String, span: Span) -> Token {
         Token {
             ttype,
             lexeme,
-            line,
-            col,
+            span,
         }
     }
 }
 
+// Comments are trivia, the lexer keeps them apart from the tokens the parser sees.
+// text is the comment as written, including its delimiters
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+}
+
 impl Debug for Token {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         write!(f, "{:?}", self.ttype)
@@ -35,14 +43,25 @@ impl Display for Token {
 pub enum TokenType {
     Lambda,
     Var,
-    Space,
-    // Very important for function application
+    // Natural number literal, sugar for a Church numeral
+    Number,
     Dot,
     LParen,
     RParen,
+    // List literals [a, b, c]
+    LBracket,
+    RBracket,
+    Comma,
     EOF,
     MetaVar,
     LeftArrow,
+    // Keywords of let x = e in b and letrec f = e in b
+    Let,
+    LetRec,
+    In,
+    // Statement separators
     Semicolon,
+    // Only outside parentheses and brackets, so definitions can span lines
+    Newline,
     Equal,
 }
\ No newline at end of file
@@ -0,0 +1,20 @@
+pub use diagnostic::Diagnostic;
+pub use error::Error;
+pub use evaluating::{Encoding, Equivalence, EvalError, Evaluator, Normalization, Redex, RedexKind, ReductionStrategy, Step};
+pub use lexing::{Comment, LexError, Lexer, Span, Token, TokenType};
+pub use parsing::{Direction, Expr, Notation, ParseError, Parser, Program, Statement};
+pub use session::Session;
+
+pub mod diagnostic;
+pub mod error;
+pub mod lexing;
+pub mod parsing;
+pub mod evaluating;
+pub mod session;
+
+// Do not input bindings here, used to create default Combinators from string
+pub(crate) fn force_evaluate(xs: &str) -> Expr {
+    let tokens = Lexer::new().lex(xs).expect("Failed to force lex");
+    Parser::new(tokens).parse().expect("Failed to force parse").remove(0).expr
+}
+
@@ -1,25 +1,183 @@
 extern crate rustyline;
 
+use std::{env, fs, process};
+use std::io::{self, IsTerminal, Read};
+
 use rustyline::Editor;
 use rustyline::error::ReadlineError;
 
-use lexing::Lexer;
+use r_lambda_calculus::{Error, Expr, Notation, Redex, RedexKind, ReductionStrategy, Session};
+
+const USAGE: &str = "\
+Usage: r_lambda_calculus [options] [file...]
+       r_lambda_calculus [options] run <file> [file...]
+       r_lambda_calculus [options] -e <expr> [-e <expr>...] [file...]
 
-use crate::evaluating::Evaluator;
-use crate::lexing::Token;
-use crate::parsing::{Parser, Expr};
+Files are loaded first, then
+  run <file>             evaluates every statement of file, printing the result of each expression
+  -e <expr>              evaluates expr and prints its result, may be repeated
+Without either, statements are read from stdin if it is not a terminal, otherwise the REPL starts.
+Outside the REPL only results are printed, and the first error exits with a non-zero status.
+Errors are coloured on a terminal unless NO_COLOR is set.
 
+Options:
+  -q, --quiet            only print results in the REPL
+  -i, --interactive      start the REPL even if stdin is not a terminal
+  -s, --strategy <name>  reduction strategy, see :help in the REPL
+      --fuel <n|off>     maximum number of reduction steps
+      --eta              also contract η-redexes
+      --no-fold          show results in full, instead of naming subterms equal to a binding
+      --unicode          print λ instead of \\ and ≡ instead of <-
+  -h, --help             print this message";
 
-mod lexing;
-mod parsing;
-mod evaluating;
+#[derive(Default)]
+struct Options {
+    files: Vec<String>,
+    run: Option<String>,
+    expressions: Vec<String>,
+    quiet: bool,
+    interactive: bool,
+    strategy: Option<ReductionStrategy>,
+    fuel: Option<Option<usize>>,
+    eta: bool,
+    no_fold: bool,
+    unicode: bool,
+}
 
 fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let mut session = Session::new();
+    if let Some(strategy) = options.strategy { session.set_strategy(strategy); }
+    if let Some(fuel) = options.fuel { session.set_fuel(fuel); }
+    session.set_eta(options.eta);
+    session.set_fold(!options.no_fold);
+    if options.unicode { session.set_notation(Notation::Unicode); }
+
+    let interactive = options.interactive
+        || (options.run.is_none() && options.expressions.is_empty() && io::stdin().is_terminal());
+
+    if interactive {
+        for path in &options.files {
+            load(&mut session, path);
+        }
+        repl(&mut session, options.quiet);
+        return;
+    }
+
+    for path in &options.files {
+        if let Err(error) = session.load_file(path) {
+            eprintln!("{}", render(&session, &error, None));
+            process::exit(1);
+        }
+    }
+
+    let succeeded = if let Some(path) = &options.run {
+        match fs::read_to_string(path) {
+            Ok(source) => run(&mut session, path, &source),
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                false
+            }
+        }
+    } else if !options.expressions.is_empty() {
+        options.expressions.iter().all(|expr| run(&mut session, "-e", expr))
+    } else {
+        let mut source = String::new();
+        match io::stdin().read_to_string(&mut source) {
+            Ok(_) => run(&mut session, "<stdin>", &source),
+            Err(error) => {
+                eprintln!("<stdin>: {}", error);
+                false
+            }
+        }
+    };
+
+    if !succeeded {
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item=String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("Missing value for {}", name));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "-q" | "--quiet" => options.quiet = true,
+            "-i" | "--interactive" => options.interactive = true,
+            "--eta" => options.eta = true,
+            "--no-fold" => options.no_fold = true,
+            "--unicode" => options.unicode = true,
+            "-e" | "--eval" => options.expressions.push(value(&arg)?),
+            "-s" | "--strategy" => options.strategy = Some(value(&arg)?.parse()?),
+            "--fuel" => options.fuel = match value(&arg)?.as_str() {
+                "off" => Some(None),
+                fuel => Some(Some(fuel.parse().map_err(|_| format!("Expected a number of steps or 'off', found {}", fuel))?)),
+            },
+            "run" if options.run.is_none() && options.files.is_empty() => options.run = Some(value(&arg)?),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => options.files.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+// Evaluates every statement of source, printing the results of expressions
+fn run(session: &mut Session, file: &str, source: &str) -> bool {
+    let statements = match session.parse_source(file, source) {
+        Ok(statements) => statements,
+        Err(error) => {
+            eprintln!("{}", render(session, &error, None));
+            return false;
+        }
+    };
+    session.declare(&statements);
+    let succeeded = statements.iter().all(|statement| match session.eval_statement(statement) {
+        Ok(value) => {
+            if !matches!(statement.expr, Expr::Binding(_, _, _)) { println!("{}", annotate(session, &value)) }
+            true
+        }
+        Err(error) => {
+            eprintln!("{}", render(session, &error.in_file(file, source), None));
+            false
+        }
+    });
+    match session.check_declared() {
+        Err(error) if succeeded => {
+            eprintln!("{}", render(session, &error.in_file(file, source), None));
+            false
+        }
+        _ => succeeded,
+    }
+}
+
+// Appends the values a result encodes as a comment, e.g. `\f.\x.f (f x) -- 2`
+fn annotate(session: &mut Session, expr: &Expr) -> String {
+    let expr_string = expr.notated(session.notation()).to_string();
+    let encodings = session.decode(expr);
+    if encodings.is_empty() {
+        return expr_string;
+    }
+    let values: Vec<String> = encodings.iter().map(ToString::to_string).collect();
+    format!("{} -- {}", expr_string, values.join(", "))
+}
+
+fn repl(session: &mut Session, quiet: bool) {
 
     println!(":help");
     let mut rl = Editor::<()>::new();
 
-    let mut evaluator = Evaluator::new();
+    let mut trace = false;
 
     loop {
         let input = match rl.readline("\\>>: ") {
@@ -43,79 +201,302 @@ fn main() {
         if input.trim_end().is_empty() { continue; }
         if input.trim_end() == "quit" || input.trim_end() == ":q" { break; }
         if input.trim_end() == ":help" || input.trim_end() == ":h" { print_help_text(); continue; }
-        if input.trim_end() == ":env" || input.trim_end() == ":e" { println!("{:?}", evaluator.env); continue; }
+        if input.trim_end() == ":env" || input.trim_end() == ":e" { println!("{:?}", session.bindings()); continue; }
+        if input.trim_end() == ":trace" || input.trim_end() == ":t" {
+            trace = !trace;
+            println!("Trace: {}", if trace { "on" } else { "off" });
+            continue;
+        }
+        if let Some(name) = command(&input, ":strategy") { set_strategy(session, name); continue; }
+        if let Some(path) = command(&input, ":load").or_else(|| command(&input, ":l")) { load(session, path); continue; }
+        if let Some(term) = command(&input, ":eta-expand") { print_eta_expansion(session, term); continue; }
+        if let Some(eta) = command(&input, ":eta") { set_eta(session, eta); continue; }
+        if let Some(terms) = command(&input, ":eq") { print_equivalence(session, terms); continue; }
+        if let Some(fold) = command(&input, ":fold") { set_fold(session, fold); continue; }
+        if let Some(unicode) = command(&input, ":unicode") { set_unicode(session, unicode); continue; }
+        if let Some(fuel) = command(&input, ":fuel") { set_fuel(session, fuel); continue; }
+        if let Some(depth) = command(&input, ":depth") { set_max_depth(session, depth); continue; }
+        if let Some(size) = command(&input, ":size") { set_max_size(session, size); continue; }
+        if input.trim_start().starts_with(':') {
+            println!("Unknown command {}, see :help", input.split_whitespace().next().unwrap_or_default());
+            continue;
+        }
 
-        let mut lexer = Lexer::new();
-        let tokens = match lexer.lex(&input) {
+        let tokens = match session.lex(&input) {
             Ok(tokens) => tokens,
-            Err(errors) => {
-                println!("{:?}", errors);
+            Err(error) => {
+                println!("{}", render(session, &error, Some(&input)));
                 continue;
             }
         };
 
-        println!("Tokens: {:?}", tokens);
+        if !quiet { println!("Tokens: {:?}", tokens); }
 
-        let mut parser = Parser::new(tokens);
-        let expr = match parser.parse() {
-            Ok(expr) => expr,
+        let program = match session.parse(tokens) {
+            Ok(program) => program,
             Err(error) => {
-                println!("{}", error);
+                println!("{}", render(session, &error, Some(&input)));
                 continue;
             }
         };
 
-//        println!("{:?}", expr);
-//        println!("{:#?}", expr);
+        session.declare(&program);
+        for statement in program {
+            if !eval_statement(session, statement.expr, &input, quiet, trace) { break; }
+        }
+        if let Err(error) = session.check_declared() {
+            println!("{}", render(session, &error, Some(&input)));
+        }
+    }
+}
+
+// Prints the statement and its value, returning whether it evaluated successfully
+fn eval_statement(session: &mut Session, expr: Expr, input: &str, quiet: bool, trace: bool) -> bool {
+    if !quiet {
         println!("Parenthesized: {:?}", expr);
-        println!("Standard: {}", expr);
+        println!("Standard: {}", expr.notated(session.notation()));
+    }
 
-//        println!("Renamed to t: {}", Evaluator::alpha_rename(&expr, &"x".to_string(), &"t".to_string()));
+    let notation = session.notation();
 
-//        evaluator.evaluate(expr);
-        let redex = match evaluator.evaluate(expr) {
-            Ok(expr) => expr,
-            Err(errors) => {
-                println!("{:?}", errors);
-                continue;
-            }
-        };
+    let result = match expr {
+        Expr::Binding(_, _, _) => session.eval(expr),
+        _ if trace => session.trace(&expr, &mut |step, expr, redex| print_step(step, expr, redex, notation)).and_then(|normalization| normalization.into_result().map_err(Error::from)),
+        _ => session.eval(expr),
+    };
 
-        println!("β-reduction (parenthesized): {:?}", redex);
-        println!("β-reduction: {}", redex);
+    let redex = match result {
+        Ok(expr) => expr,
+        Err(error) => {
+            println!("{}", render(session, &error, Some(input)));
+            return false;
+        }
+    };
 
-        println!();
+    if quiet {
+        println!("{}", annotate(session, &redex));
+        return true;
     }
+
+    println!("β-reduction (parenthesized): {:?}", redex);
+    println!("β-reduction: {}", annotate(session, &redex));
+
+    println!();
+    true
 }
 
+// The argument of the REPL command name if input is that command, e.g. file for :load file but not for :loadfile
+fn command<'a>(input: &'a str, name: &str) -> Option<&'a str> {
+    let argument = input.trim().strip_prefix(name)?;
+    if argument.is_empty() || argument.starts_with(char::is_whitespace) {
+        Some(argument.trim())
+    } else {
+        None
+    }
+}
+
+fn load(session: &mut Session, path: &str) {
+    if path.is_empty() {
+        println!("Expected a file to load");
+        return;
+    }
+    match session.load_file(path) {
+        Ok(names) => println!("Loaded {} definitions from {}: {}", names.len(), path, names.join(", ")),
+        Err(error) => println!("{}", render(session, &error, None)),
+    }
+}
+
+// Errors with the line of source they occurred on, coloured on a terminal
+fn render(session: &Session, error: &Error, source: Option<&str>) -> String {
+    error.render(source, colour(), session.notation())
+}
+
+fn colour() -> bool {
+    env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal() && io::stderr().is_terminal()
+}
+
+// Underlines the redex about to be contracted, or braces it without colour
+fn print_step(step: usize, expr: &Expr, redex: &Redex, notation: Notation) {
+    let (open, close) = if colour() { ("\x1b[4m", "\x1b[0m") } else { ("{", "}") };
+    let kind = match redex.kind {
+        RedexKind::Beta => "β",
+        RedexKind::Eta => "η",
+        RedexKind::Delta => "δ",
+    };
+    println!("{:>4} {}: {}", step, kind, expr.highlight(&redex.path, open, close).notation(notation));
+}
+
+fn set_strategy(session: &mut Session, name: &str) {
+    if name.is_empty() {
+        let strategy = session.strategy();
+        println!("Reduction strategy: {} (reduces to {})", strategy, strategy.normal_form());
+        return;
+    }
+    match name.parse::<ReductionStrategy>() {
+        Ok(strategy) => {
+            session.set_strategy(strategy);
+            println!("Reduction strategy: {} (reduces to {})", strategy, strategy.normal_form());
+        }
+        Err(error) => println!("{}", error),
+    }
+}
 
-// Do not input bindings here, used to create default Combinators from string
-fn force_evaluate(xs: &str) -> Expr {
-    let tokens = Lexer::new().lex(xs).expect("Failed to force lex");
-    let expr = Parser::new(tokens).parse().expect("Failed to force parse");
-//    Evaluator::new().beta_reduce(expr).expect("Failed to force beta-reduce")
-    expr
+// The terms are the operator and operand of a single application, i.e. :eq (B I) I
+fn print_equivalence(session: &mut Session, terms: &str) {
+    let (left, right) = match session.parse_expr(terms) {
+        Ok(Expr::Application(left, right, _)) => (left, right),
+        Ok(_) => {
+            println!("Expected two terms, parenthesize any that are not a single variable or metavariable");
+            return;
+        }
+        Err(error) => {
+            println!("{}", render(session, &error, Some(terms)));
+            return;
+        }
+    };
+    match session.alpha_equivalent(&left, &right) {
+        Ok(alpha) => println!("α-equivalence: {}", if alpha { "equivalent" } else { "not equivalent" }),
+        Err(error) => {
+            println!("{}", render(session, &error, Some(terms)));
+            return;
+        }
+    }
+    let name = if session.eta() { "βη-equivalence" } else { "β-equivalence" };
+    match session.beta_equivalent(&left, &right) {
+        Ok(beta) => println!("{}: {}", name, beta),
+        Err(error) => println!("{}", render(session, &error, Some(terms))),
+    }
 }
 
+fn print_eta_expansion(session: &mut Session, term: &str) {
+    match session.parse_expr(term).and_then(|expr| session.eta_expand(&expr)) {
+        Ok(expanded) => println!("η-expansion: {}", expanded.notated(session.notation())),
+        Err(error) => println!("{}", render(session, &error, Some(term))),
+    }
+}
 
-fn format_error(message: &str, token: &Token) -> String {
-    format!("{}:{}: {}", token.line, token.col, message)
+fn set_eta(session: &mut Session, eta: &str) {
+    match eta {
+        "" => {}
+        "on" => session.set_eta(true),
+        "off" => session.set_eta(false),
+        _ => {
+            println!("Expected 'on' or 'off', found {}", eta);
+            return;
+        }
+    }
+    println!("η-reduction: {}", if session.eta() { "on" } else { "off" });
+}
+
+fn set_fold(session: &mut Session, fold: &str) {
+    match fold {
+        "" => {}
+        "on" => session.set_fold(true),
+        "off" => session.set_fold(false),
+        _ => {
+            println!("Expected 'on' or 'off', found {}", fold);
+            return;
+        }
+    }
+    println!("Folding into names: {}", if session.fold() { "on" } else { "off" });
+}
+
+fn set_unicode(session: &mut Session, unicode: &str) {
+    match unicode {
+        "" => {}
+        "on" => session.set_notation(Notation::Unicode),
+        "off" => session.set_notation(Notation::Ascii),
+        _ => {
+            println!("Expected 'on' or 'off', found {}", unicode);
+            return;
+        }
+    }
+    println!("Unicode output: {}", if session.notation() == Notation::Unicode { "on" } else { "off" });
+}
+
+fn set_fuel(session: &mut Session, fuel: &str) {
+    match fuel {
+        "" => {}
+        "off" => session.set_fuel(None),
+        _ => match fuel.parse::<usize>() {
+            Ok(fuel) => session.set_fuel(Some(fuel)),
+            Err(_) => {
+                println!("Expected a number of steps or 'off', found {}", fuel);
+                return;
+            }
+        }
+    }
+    match session.fuel() {
+        Some(fuel) => println!("Fuel: {} steps", fuel),
+        None => println!("Fuel: unlimited"),
+    }
+}
+
+fn set_max_depth(session: &mut Session, depth: &str) {
+    if !depth.is_empty() {
+        match depth.parse::<usize>() {
+            Ok(depth) => session.set_max_depth(depth),
+            Err(_) => {
+                println!("Expected a maximum term depth, found {}", depth);
+                return;
+            }
+        }
+    }
+    println!("Maximum term depth: {}", session.max_depth());
+}
+
+fn set_max_size(session: &mut Session, size: &str) {
+    if !size.is_empty() {
+        match size.parse::<usize>() {
+            Ok(size) => session.set_max_size(size),
+            Err(_) => {
+                println!("Expected a maximum term size, found {}", size);
+                return;
+            }
+        }
+    }
+    println!("Maximum term size: {}", session.max_size());
 }
 
 fn print_help_text() {
     println!();
     println!("Help");
-    println!("Use backslash '\\' as lambda");
-    println!("Allows syntactic sugar for multiple abstractions: \\xyz.x y z -> \\x.\\y.\\z.x y z");
-    println!("Lambda variables currently must be a single lower case character, though this may change");
-    println!("Spaces are required for application");
+    println!("Use backslash '\\' or 'λ' as lambda, and '.', '->' or '→' between the variables and the body, e.g. λx → x");
+    println!("Allows syntactic sugar for multiple abstractions: \\x y z.x y z -> \\x.\\y.\\z.x y z");
+    println!("Lambda variables begin with a lower case letter of any script or '_' followed by letters, digits, '_' or primes, e.g. x, succ, x', α");
+    println!("Application is juxtaposition, whitespace between terms is optional where unambiguous, e.g. (f)(x) -> f x");
     println!("Application has higher precedence than abstraction, standard associativity rules apply");
     println!("Applying abstractions without parentheses is allowed");
     println!("i.e. \\x.x \\y.y -> \\x.(x (\\y.y))");
+    println!("Local definitions are written let x = e1 in e2, and letrec f = e in b where f may occur in e");
     println!("You are allowed to set bindings to lambda expressions");
-    println!("Metavariables can either begin with a Uppercase letter followed by english alphanumerics or a '$' followed by any english alphanumerics");
-    println!("Binding is expressed using the '=' operator and the right operand can be any lambda expression");
-    println!("Examples: $false = \\xy.y, M = \\f.f f, Foo = x");
-    println!("Some names are by default bound to combinators, {{ I, K, KI, B, T, M }} and boolean operators {{ NOT, AND, OR }}");
+    println!("Metavariables can either begin with an upper case letter of any script or a '$', followed by letters, digits, '_' or primes, e.g. FOO, $foo, Θ");
+    println!("Binding is expressed using the '=', '<-', ':=' or '≡' operator and the right operand can be any lambda expression");
+    println!("Statements on one line, or in a file, can be separated by ';', e.g. A = K; A I");
+    println!("In files statements also end at a newline, except inside parentheses or brackets, so definitions can span lines, unless the next line starts with a binding");
+    println!("Examples: $false = \\x y.y, M = \\f.f f, Foo = x");
+    println!("Some names are by default bound to combinators, {{ I, K, KI, B, C, T, M }} and booleans {{ TRUE, FALSE, NOT, AND, OR, XOR, IF, BEQ }}");
+    println!("Fixed point combinators {{ Y, Z, THETA (or Θ) }} are bound, Z for the strategies that reduce arguments first");
+    println!("Bindings may refer to themselves or to names bound later in the same file or line, e.g. FACT = \\n.IF (ISZERO n) 1 (MULT n (FACT (PRED n)))");
+    println!("These are bound unevaluated and tied through Y (Z with applicative or cbv) once used, they have no normal form, so :eq only finds them β-equivalent if they are α-equivalent");
+    println!("Other bindings keep the definitions they refer to when bound, while the names these refer to cannot be bound to something else afterwards");
+    println!("Metavariables are only unfolded (δ-reduced) once applied, so results may still refer to bindings, e.g. K I x -> I, unless folding is off");
+    println!("Natural numbers are Church numerals, 2 -> \\f.\\x.f (f x), with arithmetic {{ SUCC, PRED, PLUS, SUB, MULT, EXP, ISZERO }}");
+    println!("Lists are written [a, b, c] and encoded as their right fold, [a, b] -> \\c n.c a (c b n), with {{ NIL, CONS, HEAD, TAIL, ISNIL, MAP, FOLD, LENGTH }} and pairs {{ PAIR, FST, SND }}");
+    println!("Scott encoded lists {{ SNIL, SCONS, SHEAD, STAIL, SISNIL }} and numerals {{ SZERO, SSUCC, SPRED, SISZERO }} are also bound");
+    println!("Results that are numerals, booleans or lists are followed by the value they encode, e.g. PLUS 1 1 -> \\f.\\x.f (f x) -- 2");
     println!("Use :e or :env for see current bindings");
+    println!("Use :l or :load <path> to bind every definition in a file, files given as arguments are loaded on startup");
+    println!("Files contain bindings Name = expr, one per line or separated by ';', and blank lines");
+    println!("Comments run from -- or # to the end of the line, or from {{- to -}}, which nest, anywhere including the REPL");
+    println!("Use :strategy <name> to choose how terms are reduced, :strategy alone shows the current one");
+    println!("Use :fuel <n> or :fuel off to limit the number of reduction steps, and :depth <n> to limit how deeply terms may nest");
+    println!("Use :size <n> to limit the number of nodes terms may grow to");
+    println!("Use :eq A B to check whether A and B are α-equivalent, and β-equivalent by comparing normal forms, e.g. :eq (B I) I");
+    println!("Use :eta on or :eta off to also contract η-redexes \\x.f x -> f, in strategies that reduce under abstractions and in :eq");
+    println!("Use :fold on or :fold off to show subterms of results that equal a binding by its name, e.g. K I -> KI, or to show them in full");
+    println!("Use :unicode on or :unicode off to print λ and ≡ instead of \\ and <-");
+    println!("Use :eta-expand E to print the η-expansion \\x.E x of E");
+    println!("Use :t or :trace to toggle printing every reduction step, the redex contracted in each step is underlined, or in braces without colour");
+    println!("Strategies: normal (default), applicative, cbn (call by name), cbv (call by value), need (call by need), head (head spine)");
 }
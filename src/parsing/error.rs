@@ -0,0 +1,94 @@
+use std::fmt::{Display, Error, Formatter};
+
+use crate::lexing::{Span, TokenType};
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ParseError {
+    // A specific token was required, e.g. the in of a let
+    Expected { expected: TokenType, found: TokenType, span: Span },
+    // The binders of an abstraction not followed by its dot
+    ExpectedDot { names: Vec<String>, found: TokenType, span: Span },
+    // A parenthesis or bracket opened at open that is not closed
+    Unclosed { expected: TokenType, found: TokenType, open: Span, span: Span },
+    // A token that cannot start an expression where one is required
+    ExpectedExpression { found: TokenType, span: Span },
+    // Input left over after a complete statement
+    ExpectedEndOfStatement { found: TokenType, lexeme: String, span: Span },
+    // Only metavariables can be bound, e.g. x = I
+    BindingVariable { name: String, span: Span },
+    NumeralTooLarge { lexeme: String, max: usize, span: Span },
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::Expected { span, .. }
+            | ParseError::ExpectedDot { span, .. }
+            | ParseError::Unclosed { span, .. }
+            | ParseError::ExpectedExpression { span, .. }
+            | ParseError::ExpectedEndOfStatement { span, .. }
+            | ParseError::BindingVariable { span, .. }
+            | ParseError::NumeralTooLarge { span, .. } => *span,
+        }
+    }
+
+    // Context that explains the error
+    pub fn notes(&self) -> Vec<String> {
+        match self {
+            ParseError::Unclosed { expected: TokenType::RBracket, open, .. } =>
+                vec![format!("the list opened at {} is not closed", open)],
+            ParseError::Unclosed { open, .. } => vec![format!("the parenthesis opened at {} is not closed", open)],
+            ParseError::ExpectedEndOfStatement { .. } => vec!["statements are separated by ; or newlines".to_string()],
+            ParseError::NumeralTooLarge { .. } => vec!["every application in a numeral nests, which limits their size".to_string()],
+            _ => Vec::new(),
+        }
+    }
+
+    // A suggestion of how to fix the error
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            ParseError::ExpectedDot { names, .. } =>
+                Some(format!("did you mean `\\{}.`? The binders of an abstraction end with a dot", names.join(" "))),
+            ParseError::Expected { expected: TokenType::Var, found: TokenType::MetaVar, .. } =>
+                Some("abstractions bind variables, which start with a lower case letter or _".to_string()),
+            ParseError::Expected { expected: TokenType::Equal | TokenType::In, .. } =>
+                Some("local definitions are written let x = value in body".to_string()),
+            ParseError::ExpectedEndOfStatement { found: TokenType::RParen, .. } =>
+                Some("this parenthesis has no matching (".to_string()),
+            ParseError::ExpectedEndOfStatement { found: TokenType::Equal | TokenType::LeftArrow, .. } =>
+                Some("only a metavariable at the start of a statement can be bound".to_string()),
+            ParseError::BindingVariable { name, .. } =>
+                Some(format!("bound names are metavariables starting with an upper case letter or $, e.g. {}", metavariable(name))),
+            ParseError::NumeralTooLarge { .. } =>
+                Some("build larger numbers arithmetically, e.g. MULT 1000 1000".to_string()),
+            _ => None,
+        }
+    }
+}
+
+// x -> X, to suggest a name that can be bound instead of a variable
+fn metavariable(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_lowercase() => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        _ => format!("${}", name),
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match self {
+            ParseError::Expected { expected, found, .. } | ParseError::Unclosed { expected, found, .. } =>
+                write!(f, "Expected {:?}, found {:?}", expected, found),
+            ParseError::ExpectedDot { found, .. } => write!(f, "Expected Dot, found {:?}", found),
+            ParseError::ExpectedExpression { found, .. } => write!(f, "Expected an expression, found {:?}", found),
+            ParseError::ExpectedEndOfStatement { found, lexeme, .. } =>
+                write!(f, "Unexpected {:?} '{}', expected ; or the end of the statement", found, lexeme),
+            ParseError::BindingVariable { name, .. } => write!(f, "Cannot bind variable {}", name),
+            ParseError::NumeralTooLarge { lexeme, max, .. } =>
+                write!(f, "Numeral {} is larger than the maximum of {}", lexeme, max),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
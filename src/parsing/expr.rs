@@ -1,15 +1,22 @@
+use std::collections::HashSet;
 use std::fmt::{Display, Error, Formatter, Debug};
 
-use crate::lexing::Token;
+use crate::lexing::{Span, Token};
 
+// Every node records the part of the source it was parsed from, metavariables in their token.
+// Nodes that do not come from the source, e.g. results of reduction, have Span::NONE
 #[derive(Clone)]
 pub enum Expr {
-    Variable(String),
-    Abstraction(String, Box<Expr>),
-    Application(Box<Expr>, Box<Expr>),
-    Grouping(Box<Expr>),
-    Binding(String, Box<Expr>),
+    Variable(String, Span),
+    Abstraction(String, Box<Expr>, Span),
+    Application(Box<Expr>, Box<Expr>, Span),
+    Grouping(Box<Expr>, Span),
+    Binding(String, Box<Expr>, Span),
     MetaVariable(Token),
+    // let x = e1 in e2, sugar for (\x.e2) e1
+    Let(String, Box<Expr>, Box<Expr>, Span),
+    // letrec f = e in b, where f may occur in e, sugar for (\f.b) (Y (\f.e))
+    LetRec(String, Box<Expr>, Box<Expr>, Span),
 }
 
 impl Debug for Expr {
@@ -18,26 +25,329 @@ impl Debug for Expr {
             write!(f, "Not implemented")
         } else {
             match self {
-                Expr::Variable(name) => write!(f, "{}", name),
-                Expr::Abstraction(var, expr) => write!(f, "(\\{}.({:?}))", var, expr),
-                Expr::Application(left, right) => write!(f, "({:?} {:?})", left, right),
-                Expr::Grouping(expr) => write!(f, "({:?})", expr),
-                Expr::Binding(name, expr) => write!(f, "{} <- {:?}", name, expr),
+                Expr::Variable(name, _) => write!(f, "{}", name),
+                Expr::Abstraction(var, expr, _) => write!(f, "(\\{}.({:?}))", var, expr),
+                Expr::Application(left, right, _) => write!(f, "({:?} {:?})", left, right),
+                Expr::Grouping(expr, _) => write!(f, "({:?})", expr),
+                Expr::Binding(name, expr, _) => write!(f, "{} <- {:?}", name, expr),
                 Expr::MetaVariable(token) => write!(f, "{}", token.lexeme),
+                Expr::Let(name, value, body, _) => write!(f, "(let {} = ({:?}) in ({:?}))", name, value, body),
+                Expr::LetRec(name, value, body, _) => write!(f, "(letrec {} = ({:?}) in ({:?}))", name, value, body),
             }
         }
     }
 }
 
+// A step from a node to one of its children
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Direction {
+    // Left of an application
+    Function,
+    // Right of an application
+    Argument,
+    // Body of an abstraction
+    Body,
+}
+
+// How lambdas and bindings are printed, both can be parsed back
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub enum Notation {
+    // \x.x and X <- e
+    #[default]
+    Ascii,
+    // λx.x and X ≡ e
+    Unicode,
+}
+
+impl Notation {
+    fn lambda(self) -> &'static str {
+        match self {
+            Notation::Ascii => "\\",
+            Notation::Unicode => "λ",
+        }
+    }
+
+    fn binding(self) -> &'static str {
+        match self {
+            Notation::Ascii => "<-",
+            Notation::Unicode => "≡",
+        }
+    }
+}
+
+// Displays an expression in the given notation, Display itself uses ASCII
+pub struct Notated<'a> {
+    expr: &'a Expr,
+    notation: Notation,
+}
+
+// Displays an expression with the subterm at path wrapped in open and close,
+// e.g. terminal escape codes
+pub struct Highlighted<'a> {
+    expr: &'a Expr,
+    path: &'a [Direction],
+    open: &'a str,
+    close: &'a str,
+    notation: Notation,
+}
+
+impl Highlighted<'_> {
+    pub fn notation(self, notation: Notation) -> Self {
+        Highlighted { notation, ..self }
+    }
+}
+
 impl Display for Expr {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.fmt_standard(f, true, None, Notation::Ascii)
+    }
+}
+
+impl Display for Notated<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.expr.fmt_standard(f, true, None, self.notation)
+    }
+}
+
+impl Display for Highlighted<'_> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        self.expr.fmt_standard(f, true, Some(self), self.notation)
+    }
+}
+
+impl Expr {
+    // \f.\x.f (f ... (f x)) with n applications of f, every node spanning the literal
+    pub fn numeral(n: usize, span: Span) -> Expr {
+        let mut body = Expr::Variable("x".to_string(), span);
+        for _ in 0..n {
+            body = Expr::Application(Box::new(Expr::Variable("f".to_string(), span)), Box::new(body), span);
+        }
+        Expr::Abstraction("f".to_string(), Box::new(Expr::Abstraction("x".to_string(), Box::new(body), span)), span)
+    }
+
+    // \c.\n.c a (c b n) for [a, b], the right fold of the elements.
+    // The nodes introduced span the whole literal, the elements keep their own spans
+    pub fn list(elements: Vec<Expr>, span: Span) -> Expr {
+        // Binders must not capture variables of the elements
+        let mut names = HashSet::new();
+        elements.iter().for_each(|element| element.names(&mut names));
+        let fresh = |name: &str| {
+            let mut name = name.to_string();
+            while names.contains(&name) {
+                name.push('\'');
+            }
+            name
+        };
+        let (cons, nil) = (fresh("c"), fresh("n"));
+
+        let mut body = Expr::Variable(nil.clone(), span);
+        for element in elements.into_iter().rev() {
+            body = Expr::Application(
+                Box::new(Expr::Application(Box::new(Expr::Variable(cons.clone(), span)), Box::new(element), span)),
+                Box::new(body),
+                span,
+            );
+        }
+        Expr::Abstraction(cons, Box::new(Expr::Abstraction(nil, Box::new(body), span)), span)
+    }
+
+    // The part of the source the expression was parsed from
+    pub fn span(&self) -> Span {
         match self {
-            Expr::Variable(name) => write!(f, "{}", name),
-            Expr::Abstraction(var, expr) => write!(f, "\\{}.{}", var, expr),
-            Expr::Application(left, right) => write!(f, "{} {}", left, right),
-            Expr::Grouping(expr) => write!(f, "({})", expr),
-            Expr::Binding(name, expr) => write!(f, "{} <- {}", name, expr),
+            Expr::Variable(_, span)
+            | Expr::Abstraction(_, _, span)
+            | Expr::Application(_, _, span)
+            | Expr::Grouping(_, span)
+            | Expr::Binding(_, _, span)
+            | Expr::Let(_, _, _, span)
+            | Expr::LetRec(_, _, _, span) => *span,
+            Expr::MetaVariable(token) => token.span,
+        }
+    }
+
+    // The same expression with every node spanning span,
+    // e.g. for a definition expanded where it is referenced
+    pub fn respan(&self, span: Span) -> Expr {
+        let respan = |expr: &Expr| Box::new(expr.respan(span));
+        match self {
+            Expr::Variable(name, _) => Expr::Variable(name.clone(), span),
+            Expr::Abstraction(name, body, _) => Expr::Abstraction(name.clone(), respan(body), span),
+            Expr::Application(left, right, _) => Expr::Application(respan(left), respan(right), span),
+            Expr::Grouping(expr, _) => Expr::Grouping(respan(expr), span),
+            Expr::Binding(name, expr, _) => Expr::Binding(name.clone(), respan(expr), span),
+            Expr::MetaVariable(token) => Expr::MetaVariable(Token { span, ..token.clone() }),
+            Expr::Let(name, value, body, _) => Expr::Let(name.clone(), respan(value), respan(body), span),
+            Expr::LetRec(name, value, body, _) => Expr::LetRec(name.clone(), respan(value), respan(body), span),
+        }
+    }
+
+    // Whether no metavariables, bindings or lets are left, as after expansion
+    pub fn is_expanded(&self) -> bool {
+        match self {
+            Expr::Variable(_, _) => true,
+            Expr::Abstraction(_, expr, _) | Expr::Grouping(expr, _) => expr.is_expanded(),
+            Expr::Application(left, right, _) => left.is_expanded() && right.is_expanded(),
+            Expr::Binding(_, _, _) | Expr::MetaVariable(_) | Expr::Let(_, _, _, _) | Expr::LetRec(_, _, _, _) => false,
+        }
+    }
+
+    // Whether a variable called name occurs in the expression and no abstraction binds it
+    pub fn occurs_free(&self, name: &str) -> bool {
+        match self {
+            Expr::Variable(x, _) => x == name,
+            Expr::Abstraction(x, body, _) => x != name && body.occurs_free(name),
+            Expr::Application(left, right, _) => left.occurs_free(name) || right.occurs_free(name),
+            Expr::Grouping(expr, _) | Expr::Binding(_, expr, _) => expr.occurs_free(name),
+            Expr::Let(x, value, body, _) => value.occurs_free(name) || (x != name && body.occurs_free(name)),
+            Expr::LetRec(x, value, body, _) => x != name && (value.occurs_free(name) || body.occurs_free(name)),
+            Expr::MetaVariable(_) => false,
+        }
+    }
+
+    // Whether a metavariable called name occurs in the expression
+    pub fn refers_to(&self, name: &str) -> bool {
+        match self {
+            Expr::Variable(_, _) => false,
+            Expr::MetaVariable(token) => token.lexeme == name,
+            Expr::Abstraction(_, expr, _) | Expr::Grouping(expr, _) | Expr::Binding(_, expr, _) => expr.refers_to(name),
+            Expr::Application(left, right, _) | Expr::Let(_, left, right, _) | Expr::LetRec(_, left, right, _) =>
+                left.refers_to(name) || right.refers_to(name),
+        }
+    }
+
+    // hint, primed until no variable of the expression, bound or free, has that name
+    pub fn fresh_name(&self, hint: &str) -> String {
+        let mut names = HashSet::new();
+        self.names(&mut names);
+        let mut name = hint.to_string();
+        while names.contains(&name) {
+            name.push('\'');
+        }
+        name
+    }
+
+    // Renames the free occurrences of the variable from, to must not occur in the expression
+    pub fn rename_free(&self, from: &str, to: &str) -> Expr {
+        let rename = |expr: &Expr| Box::new(expr.rename_free(from, to));
+        match self {
+            Expr::Variable(name, span) if name == from => Expr::Variable(to.to_string(), *span),
+            Expr::Variable(_, _) | Expr::MetaVariable(_) => self.clone(),
+            Expr::Abstraction(name, _, _) if name == from => self.clone(),
+            Expr::Abstraction(name, body, span) => Expr::Abstraction(name.clone(), rename(body), *span),
+            Expr::Application(left, right, span) => Expr::Application(rename(left), rename(right), *span),
+            Expr::Grouping(expr, span) => Expr::Grouping(rename(expr), *span),
+            Expr::Binding(name, expr, span) => Expr::Binding(name.clone(), rename(expr), *span),
+            Expr::Let(name, value, body, span) => Expr::Let(
+                name.clone(),
+                rename(value),
+                if name == from { body.clone() } else { rename(body) },
+                *span,
+            ),
+            Expr::LetRec(name, _, _, _) if name == from => self.clone(),
+            Expr::LetRec(name, value, body, span) => Expr::LetRec(name.clone(), rename(value), rename(body), *span),
+        }
+    }
+
+    // Every variable name occurring in the expression, bound or free
+    fn names(&self, acc: &mut HashSet<String>) {
+        match self {
+            Expr::Variable(name, _) => {
+                acc.insert(name.clone());
+            }
+            Expr::Abstraction(name, body, _) | Expr::Binding(name, body, _) => {
+                acc.insert(name.clone());
+                body.names(acc);
+            }
+            Expr::Application(left, right, _) => {
+                left.names(acc);
+                right.names(acc);
+            }
+            Expr::Grouping(expr, _) => expr.names(acc),
+            Expr::Let(name, value, body, _) | Expr::LetRec(name, value, body, _) => {
+                acc.insert(name.clone());
+                value.names(acc);
+                body.names(acc);
+            }
+            Expr::MetaVariable(_) => {}
+        }
+    }
+
+    // Groupings are transparent, paths only step through abstractions and applications
+    pub fn subterm(&self, path: &[Direction]) -> Option<&Expr> {
+        match (self, path.first()) {
+            (Expr::Grouping(expr, _), _) => expr.subterm(path),
+            (_, None) => Some(self),
+            (Expr::Application(left, _, _), Some(Direction::Function)) => left.subterm(&path[1..]),
+            (Expr::Application(_, right, _), Some(Direction::Argument)) => right.subterm(&path[1..]),
+            (Expr::Abstraction(_, body, _), Some(Direction::Body)) => body.subterm(&path[1..]),
+            _ => None,
+        }
+    }
+
+    pub fn highlight<'a>(&'a self, path: &'a [Direction], open: &'a str, close: &'a str) -> Highlighted<'a> {
+        Highlighted { expr: self, path, open, close, notation: Notation::Ascii }
+    }
+
+    pub fn notated(&self, notation: Notation) -> Notated<'_> {
+        Notated { expr: self, notation }
+    }
+
+    // Abstractions extend as far right as possible, so they only need parentheses
+    // when something follows them, i.e. when they are not in tail position
+    fn fmt_standard(&self, f: &mut Formatter, tail: bool, highlight: Option<&Highlighted>, notation: Notation) -> Result<(), Error> {
+        if let Expr::Grouping(expr, _) = self {
+            write!(f, "(")?;
+            expr.fmt_standard(f, true, highlight, notation)?;
+            return write!(f, ")");
+        }
+        if let Some(highlight) = highlight.filter(|highlight| highlight.path.is_empty()) {
+            write!(f, "{}", highlight.open)?;
+            self.fmt_standard(f, tail, None, notation)?;
+            return write!(f, "{}", highlight.close);
+        }
+        // Narrows the highlight to the child in the given direction, if it lies there
+        let child = |direction: Direction| highlight
+            .filter(|highlight| highlight.path[0] == direction)
+            .map(|highlight| Highlighted { path: &highlight.path[1..], ..*highlight });
+
+        match self {
+            Expr::Variable(name, _) => write!(f, "{}", name),
+            Expr::Abstraction(var, expr, _) => {
+                if !tail { write!(f, "(")?; }
+                write!(f, "{}{}.", notation.lambda(), var)?;
+                expr.fmt_standard(f, true, child(Direction::Body).as_ref(), notation)?;
+                if !tail { write!(f, ")")?; }
+                Ok(())
+            }
+            Expr::Application(left, right, _) => {
+                left.fmt_standard(f, false, child(Direction::Function).as_ref(), notation)?;
+                write!(f, " ")?;
+                let right_highlight = child(Direction::Argument);
+                if let Expr::Application(_, _, _) = **right {
+                    write!(f, "(")?;
+                    right.fmt_standard(f, true, right_highlight.as_ref(), notation)?;
+                    write!(f, ")")
+                } else {
+                    right.fmt_standard(f, tail, right_highlight.as_ref(), notation)
+                }
+            }
+            Expr::Binding(name, expr, _) => {
+                write!(f, "{} {} ", name, notation.binding())?;
+                expr.fmt_standard(f, true, None, notation)
+            }
             Expr::MetaVariable(token) => write!(f, "{}", token.lexeme),
+            // Like abstractions, lets extend as far right as possible
+            Expr::Let(name, value, body, _) | Expr::LetRec(name, value, body, _) => {
+                let keyword = if let Expr::Let(_, _, _, _) = self { "let" } else { "letrec" };
+                if !tail { write!(f, "(")?; }
+                write!(f, "{} {} = ", keyword, name)?;
+                value.fmt_standard(f, true, None, notation)?;
+                write!(f, " in ")?;
+                body.fmt_standard(f, true, None, notation)?;
+                if !tail { write!(f, ")")?; }
+                Ok(())
+            }
+            Expr::Grouping(_, _) => unreachable!(),
         }
     }
-}
\ No newline at end of file
+}
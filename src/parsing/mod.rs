@@ -1,6 +1,10 @@
-pub use expr::Expr;
+pub use error::ParseError;
+pub use expr::{Direction, Expr, Highlighted, Notated, Notation};
 pub use parser::Parser;
+pub use program::{Program, Statement};
 
+pub mod error;
 pub mod parser;
 pub mod expr;
+pub mod program;
 
@@ -1,5 +1,5 @@
-use crate::lexing::token::{Token, TokenType};
-use crate::parsing::Expr;
+use crate::lexing::{Span, Token, TokenType};
+use crate::parsing::{Expr, ParseError, Program, Statement};
 
 pub struct Parser {
     i: usize,
@@ -15,112 +15,196 @@ impl Parser {
         }
     }
 
-    pub fn parse(&mut self) -> Result<Expr, String> {
-        self.desugar_abstraction();
-        self.parse_expression()
+    pub fn parse(&mut self) -> Result<Program, ParseError> {
+        let (program, mut errors) = self.parse_program(false);
+        match errors.is_empty() {
+            true => Ok(program),
+            false => Err(errors.remove(0)),
+        }
     }
 
-    fn parse_expression(&mut self) -> Result<Expr, String> {
+    // Continues after a statement that fails to parse at the next separator,
+    // returning the statements that did parse along with every error
+    pub fn parse_recovering(&mut self) -> (Program, Vec<ParseError>) {
+        self.parse_program(true)
+    }
 
-        self.parse_binding()
-//        let expr = self.parse_binding()?;
-//        if self.i >= self.tokens.len() - 2 {
-//            Ok(expr)
-//        } else {
-//            Err(self.format_error(format!("Parser didn't consume entire input, stopped at {}", self.current().lexeme)))
-//        }
+    // <program> ::= [ <binding> ] { <separator> [ <binding> ] } EOF
+    fn parse_program(&mut self, recover: bool) -> (Program, Vec<ParseError>) {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            if self.current().ttype == TokenType::EOF { break; }
+            // Empty statements, e.g. after a trailing semicolon or a blank line
+            if self.match_separator() { continue; }
+            let start = self.current().span;
+            match self.parse_statement() {
+                Ok(expr) => statements.push(Statement { span: self.span_from(start), expr }),
+                Err(error) => {
+                    errors.push(error);
+                    if !recover { break; }
+                    self.synchronize();
+                }
+            }
+        }
+        (statements, errors)
+    }
 
+    // A statement has to end at a ;, a newline or the end of the input
+    fn parse_statement(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.parse_expression()?;
+        // The separator is left to parse_program, so the statement's span ends before it
+        if matches!(self.current().ttype, TokenType::Semicolon | TokenType::Newline | TokenType::EOF) {
+            return Ok(expr);
+        }
+        let Token { ttype, lexeme, span } = self.current().clone();
+        Err(ParseError::ExpectedEndOfStatement { found: ttype, lexeme, span })
+    }
+
+    // Skips past the next separator or to the end of the input
+    fn synchronize(&mut self) {
+        while self.current().ttype != TokenType::EOF {
+            if self.match_separator() { break; }
+            self.i += 1;
+        }
+    }
 
+    fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+        self.parse_binding()
     }
 
-    // <binding> ::= <metavar> = <binding> | <abstraction>
-    fn parse_binding(&mut self) -> Result<Expr, String> {
-        if self.r#match(TokenType::MetaVar) {
-            let name = self.previous().lexeme.clone();
-            if !self.match_next_non_space(TokenType::Equal) {
-                // Undo advance from match
-                self.i -= 1;
-                return self.parse_abstraction();
-            }
-            self.ignore_space();
-            self.expect(TokenType::Equal)?;
-            self.ignore_space();
+    // <binding> ::= <metavar> = <binding> | <metavar> <- <binding> | <abstraction>
+    fn parse_binding(&mut self) -> Result<Expr, ParseError> {
+        if self.current().ttype == TokenType::MetaVar && self.is_binding_operator_next() {
+            let Token { lexeme: name, span: start, .. } = self.current().clone();
+            self.i += 2;
             let right = self.parse_binding()?;
-            Ok(Expr::Binding(name, Box::new(right)))
+            Ok(Expr::Binding(name, Box::new(right), self.span_from(start)))
+        } else if self.current().ttype == TokenType::Var && self.is_binding_operator_next() {
+            let Token { lexeme, span, .. } = self.current().clone();
+            Err(ParseError::BindingVariable { name: lexeme, span })
         } else {
             self.parse_abstraction()
         }
     }
 
-    // <abstraction> ::= \<var>.<abstraction>
-    fn parse_abstraction(&mut self) -> Result<Expr, String> {
+    // <abstraction> ::= \<var> { <var> }.<abstraction> | <let>
+    // where \x y z.E is sugar for \x.\y.\z.E
+    fn parse_abstraction(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current().span;
         if self.r#match(TokenType::Lambda) {
-            let name = self.expect(TokenType::Var)?.lexeme.clone();
-            self.expect(TokenType::Dot)?;
-            let right = self.parse_abstraction()?;
-            Ok(Expr::Abstraction(name, Box::new(right)))
+            let mut names = vec![self.expect(TokenType::Var)?.lexeme.clone()];
+            while self.r#match(TokenType::Var) {
+                names.push(self.previous().lexeme.clone());
+            }
+            if !self.r#match(TokenType::Dot) {
+                let Token { ttype, span, .. } = *self.current();
+                return Err(ParseError::ExpectedDot { names, found: ttype, span });
+            }
+            let body = self.parse_abstraction()?;
+            // Every abstraction of the sugar spans the whole of it
+            let span = self.span_from(start);
+            Ok(names.into_iter().rev().fold(body, |body, name| Expr::Abstraction(name, Box::new(body), span)))
+        } else if self.r#match(TokenType::Let) || self.r#match(TokenType::LetRec) {
+            self.parse_let()
         } else {
             self.parse_application()
         }
     }
 
-//    Allowing syntactic sugar
-//    // <abstraction> ::= \<var>+.<abstraction>
-//    fn parse_abstraction(&mut self) -> Result<Expr, String> {
-//        if self.r#match(TokenType::Lambda) {
-//            self.expect(TokenType::Var)?.lexeme.clone();
-//            self.i -= 1;
-//            while self.r#match(TokenType::Var) {
-//                let name = self.previous().lexeme.clone();
-//                Ok(Expr::Abstraction(name, Box::new(self.parse_abstraction()?)))
-//            }
-//            self.expect(TokenType::Dot)?;
-//            let right = self.parse_abstraction()?;
-//            Ok(Expr::Abstraction(name, Box::new(right)))
-//        } else {
-//            self.parse_application()
-//        }
-//    }
-
-    // <application> ::= <primary> { < > <primary> }
-    fn parse_application(&mut self) -> Result<Expr, String> {
-        let mut expr = self.parse_primary();
-        while self.r#match(TokenType::Space) {
+    // <let> ::= let <var> = <abstraction> in <abstraction> | letrec <var> = <abstraction> in <abstraction>
+    fn parse_let(&mut self) -> Result<Expr, ParseError> {
+        let Token { ttype, span: start, .. } = *self.previous();
+        let recursive = ttype == TokenType::LetRec;
+        let name = self.expect(TokenType::Var)?.lexeme.clone();
+        self.expect(TokenType::Equal)?;
+        let value = self.parse_abstraction()?;
+        self.expect(TokenType::In)?;
+        let body = self.parse_abstraction()?;
+        let span = self.span_from(start);
+        if recursive {
+            Ok(Expr::LetRec(name, Box::new(value), Box::new(body), span))
+        } else {
+            Ok(Expr::Let(name, Box::new(value), Box::new(body), span))
+        }
+    }
+
+    // <application> ::= <primary> { <primary> }
+    fn parse_application(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current().span;
+        let mut expr = self.parse_primary()?;
+        // Application is juxtaposition, anything that cannot start a primary ends it, e.g. the in of a let
+        while Parser::starts_primary(self.current().ttype) {
             let right = self.parse_primary()?;
-            expr = Ok(Expr::Application(
-                Box::new(expr?),
-                Box::new(right),
-            ));
+            expr = Expr::Application(Box::new(expr), Box::new(right), self.span_from(start));
         }
-        expr
+        Ok(expr)
     }
 
-    // <primary> ::= <variable> | ( <expr> )
-    fn parse_primary(&mut self) -> Result<Expr, String> {
+    // <primary> ::= <variable> | <metavar> | <number> | <list> | ( <expr> )
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
         if self.r#match(TokenType::LParen) {
+            let open = self.previous().span;
             let expr = self.parse_expression()?;
-            self.expect(TokenType::RParen)?;
-            Ok(Expr::Grouping(Box::new(expr)))
+            self.expect_closing(TokenType::RParen, open)?;
+            Ok(Expr::Grouping(Box::new(expr), self.span_from(open)))
         } else if self.r#match(TokenType::Var) {
-            let name = self.previous().lexeme.clone();
-            Ok(Expr::Variable(name))
+            let Token { lexeme, span, .. } = self.previous();
+            Ok(Expr::Variable(lexeme.clone(), *span))
         } else if self.r#match(TokenType::MetaVar) {
             let token = self.previous().clone();
             Ok(Expr::MetaVariable(token))
-        } else if self.r#match(TokenType::Lambda) {
+        } else if self.r#match(TokenType::Number) {
+            self.parse_numeral()
+        } else if self.r#match(TokenType::LBracket) {
+            self.parse_list()
+        } else if self.r#match(TokenType::Lambda) || self.r#match(TokenType::Let) || self.r#match(TokenType::LetRec) {
             // Think this is correct?
             // Allows lambda abstraction as second argument of application
             self.i -= 1;
             self.parse_abstraction()
         } else {
-            let current = self.current();
-            Err(format!("{}:{}: Failed to parse primary, unexpected token {}", current.line, current.col, current))
+            let Token { ttype, span, .. } = *self.current();
+            Err(ParseError::ExpectedExpression { found: ttype, span })
         }
     }
 }
 
+// Literals
+impl Parser {
+    // Every application in a numeral nests, so this keeps its depth reasonable
+    const MAX_NUMERAL: usize = 1_000;
+
+    fn parse_numeral(&mut self) -> Result<Expr, ParseError> {
+        let Token { lexeme, span, .. } = self.previous();
+        match lexeme.parse::<usize>() {
+            Ok(n) if n <= Parser::MAX_NUMERAL => Ok(Expr::numeral(n, *span)),
+            _ => Err(ParseError::NumeralTooLarge { lexeme: lexeme.clone(), max: Parser::MAX_NUMERAL, span: *span }),
+        }
+    }
+
+    // <list> ::= [ ] | [ <abstraction> { , <abstraction> } ]
+    fn parse_list(&mut self) -> Result<Expr, ParseError> {
+        let open = self.previous().span;
+        let mut elements = Vec::new();
+        if !self.r#match(TokenType::RBracket) {
+            loop {
+                elements.push(self.parse_abstraction()?);
+                if !self.r#match(TokenType::Comma) { break; }
+            }
+            self.expect_closing(TokenType::RBracket, open)?;
+        }
+        Ok(Expr::list(elements, self.span_from(open)))
+    }
+}
+
 // Utility
 impl Parser {
+    fn starts_primary(ttype: TokenType) -> bool {
+        matches!(ttype, TokenType::LParen | TokenType::Var | TokenType::MetaVar | TokenType::Number
+            | TokenType::LBracket | TokenType::Lambda | TokenType::Let | TokenType::LetRec)
+    }
+
     fn current(&self) -> &Token {
         &self.tokens[self.i]
     }
@@ -136,48 +220,35 @@ impl Parser {
         } else { false }
     }
 
-    fn expect(&mut self, ttype: TokenType) -> Result<&Token, String> {
+    fn expect(&mut self, ttype: TokenType) -> Result<&Token, ParseError> {
         if self.r#match(ttype) {
             Ok(self.previous())
         } else {
-            Err(self.format_error(format!("expected {:?}, found {:?}", ttype, self.current().ttype)))
+            Err(ParseError::Expected { expected: ttype, found: self.current().ttype, span: self.current().span })
         }
     }
 
-    fn match_next_non_space(&mut self, ttype: TokenType) -> bool {
-        let mut i = self.i;
-        while self.tokens[i].ttype == TokenType::Space {
-            i += 1;
-        }
-        if i < self.tokens.len() && self.tokens[i].ttype == ttype {
-            self.i = i;
-            true
-        } else { false }
+    // From start to the end of the last token consumed
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous().span)
     }
 
-    fn ignore_space(&mut self) {
-        while self.current().ttype == TokenType::Space {
-            self.i += 1;
+    // Like expect, for the closing delimiter of one opened at open
+    fn expect_closing(&mut self, ttype: TokenType, open: Span) -> Result<(), ParseError> {
+        if self.r#match(ttype) {
+            Ok(())
+        } else {
+            Err(ParseError::Unclosed { expected: ttype, found: self.current().ttype, open, span: self.current().span })
         }
     }
 
-    fn format_error(&self, message: String) -> String {
-        let Token { line, col, .. } = self.current();
-        format!("error {}:{}: {}", line, col, message)
+    fn match_separator(&mut self) -> bool {
+        self.r#match(TokenType::Semicolon) || self.r#match(TokenType::Newline)
     }
 
-    // \xyz.E -> \x.\y.\z.E
-    fn desugar_abstraction(&mut self) {
-        let mut i = 0;
-        while i + 1 < self.tokens.len() { // Last token is EOF -> ignore
-            let x = &self.tokens[i];
-            i += 1;
-            if x.ttype != TokenType::Lambda { continue; }
-            while i + 1< self.tokens.len() && self.tokens[i + 1].ttype == TokenType::Var {
-                self.tokens.insert(i + 1, Token::new(TokenType::Dot, ".".to_string(), -1, -1));
-                self.tokens.insert(i + 2, Token::new(TokenType::Lambda, "\\".to_string(), -1, -1));
-                i += 3;
-            }
-        }
+    // Whether = or <- follows the current token
+    fn is_binding_operator_next(&self) -> bool {
+        self.current().ttype != TokenType::EOF
+            && matches!(self.tokens[self.i + 1].ttype, TokenType::Equal | TokenType::LeftArrow)
     }
-}
\ No newline at end of file
+}
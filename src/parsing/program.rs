@@ -0,0 +1,11 @@
+use crate::lexing::Span;
+use crate::parsing::Expr;
+
+// A binding or an expression, with the part of the source it was parsed from
+pub struct Statement {
+    pub span: Span,
+    pub expr: Expr,
+}
+
+// Statements separated by ; or newlines
+pub type Program = Vec<Statement>;
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::Error;
+use crate::evaluating::{Encoding, Equivalence, EvalError, Evaluator, Normalization, Observer, ReductionStrategy, Step};
+use crate::lexing::{Comment, Lexer, Token};
+use crate::parsing::{Expr, Notation, Parser, Program, Statement};
+
+/// Entry point for embedding the interpreter.
+///
+/// A session owns an `Evaluator` and therefore its environment of bindings,
+/// which persists across calls.
+pub struct Session {
+    evaluator: Evaluator,
+    notation: Notation,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::with_evaluator(Evaluator::new())
+    }
+
+    pub fn with_evaluator(evaluator: Evaluator) -> Session {
+        Session {
+            evaluator,
+            notation: Notation::default(),
+        }
+    }
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+impl Session {
+    pub fn lex(&self, input: &str) -> Result<Vec<Token>, Error> {
+        Ok(self.lex_with_comments(input)?.0)
+    }
+
+    /// Like `lex`, also returning the comments, which are not tokens, in order.
+    pub fn lex_with_comments(&self, input: &str) -> Result<(Vec<Token>, Vec<Comment>), Error> {
+        let mut lexer = Lexer::new();
+        let tokens = lexer.lex(input)?;
+        Ok((tokens, lexer.comments().to_vec()))
+    }
+
+    /// Parses statements separated by `;`.
+    pub fn parse(&self, tokens: Vec<Token>) -> Result<Program, Error> {
+        Ok(Parser::new(tokens).parse()?)
+    }
+
+    pub fn parse_str(&self, input: &str) -> Result<Program, Error> {
+        let tokens = self.lex(input)?;
+        self.parse(tokens)
+    }
+
+    /// Parses input that must consist of exactly one statement.
+    pub fn parse_expr(&self, input: &str) -> Result<Expr, Error> {
+        let mut program = self.parse_str(input)?;
+        if program.len() != 1 {
+            return Err(Error::StatementCount { found: program.len() });
+        }
+        Ok(program.remove(0).expr)
+    }
+
+    /// Replaces every metavariable in `expr` with its binding, without reducing.
+    pub fn expand(&mut self, expr: &Expr) -> Result<Expr, Error> {
+        Ok(self.evaluator.expand(expr)?)
+    }
+
+    /// Evaluates an already parsed expression.
+    /// Bindings are added to the environment and evaluate to their bound value.
+    pub fn eval(&mut self, expr: Expr) -> Result<Expr, Error> {
+        Ok(self.evaluator.evaluate(expr)?)
+    }
+
+    /// Reduces `expr`, reporting a partially reduced term
+    /// instead of an error if the fuel or depth limit is hit.
+    ///
+    /// Metavariables are unfolded only once applied, so the result may still refer to bindings.
+    pub fn normalize(&mut self, expr: &Expr) -> Result<Normalization, Error> {
+        self.evaluator.check_bound(expr)?;
+        let expr = self.evaluator.bind_nested(expr)?;
+        Ok(self.evaluator.normalize(&expr))
+    }
+
+    /// Like `normalize`, calling `observer` before every step with the step's number,
+    /// the current term and the position of the redex about to be contracted.
+    pub fn trace(&mut self, expr: &Expr, observer: &mut Observer) -> Result<Normalization, Error> {
+        self.evaluator.check_bound(expr)?;
+        let expr = self.evaluator.bind_nested(expr)?;
+        Ok(self.evaluator.normalize_with(&expr, Some(observer)))
+    }
+
+    /// Contracts a single redex of `expr`, `None` if there is none left.
+    /// Bindings nested in `expr` are not bound, they stand for their value.
+    pub fn step(&mut self, expr: &Expr) -> Result<Option<Step>, Error> {
+        self.evaluator.check_bound(expr)?;
+        Ok(self.evaluator.step(expr))
+    }
+
+    /// Whether both expressions are equal up to renaming of bound variables, once expanded.
+    pub fn alpha_equivalent(&mut self, left: &Expr, right: &Expr) -> Result<bool, Error> {
+        let left = self.evaluator.expand(left)?;
+        let right = self.evaluator.expand(right)?;
+        Ok(Evaluator::alpha_equivalent(&left, &right))
+    }
+
+    /// Whether both expressions have the same normal form, within the limits.
+    pub fn beta_equivalent(&mut self, left: &Expr, right: &Expr) -> Result<Equivalence, Error> {
+        let left = self.evaluator.expand(left)?;
+        let right = self.evaluator.expand(right)?;
+        Ok(self.evaluator.beta_equivalent(&left, &right))
+    }
+
+    /// `\x.E x` for a variable `x` that is not free in `E`.
+    /// Metavariables are kept, e.g. `K` -> `\x.K x`.
+    pub fn eta_expand(&mut self, expr: &Expr) -> Result<Expr, Error> {
+        self.evaluator.check_bound(expr)?;
+        Ok(Evaluator::eta_expand(&self.evaluator.desugar(expr)))
+    }
+
+    /// The Church encodings a result is alpha-equivalent to once expanded,
+    /// such as the numeral it represents.
+    pub fn decode(&mut self, expr: &Expr) -> Vec<Encoding> {
+        match self.evaluator.expand(expr) {
+            Ok(expanded) => Evaluator::decode(&expanded),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Evaluates every statement of `input` in order, returning the value of the last one.
+    /// Bindings may refer to names bound later in `input`, see `declare`.
+    pub fn eval_str(&mut self, input: &str) -> Result<Expr, Error> {
+        let program = self.parse_str(input)?;
+        self.declare(&program);
+        let mut value = Err(Error::StatementCount { found: 0 });
+        for statement in program {
+            value = self.eval(statement.expr);
+            if value.is_err() { break; }
+        }
+        let declared = self.check_declared();
+        value.and_then(|value| declared.map(|_| value))
+    }
+
+    /// Declares the names `program` binds, so its bindings may refer to names bound later in it.
+    /// Such bindings are deferred, call `check_declared` once the program is evaluated.
+    pub fn declare(&mut self, program: &Program) {
+        for statement in program {
+            if let Expr::Binding(name, _, _) = &statement.expr {
+                self.evaluator.declared.insert(name.clone());
+            }
+        }
+    }
+
+    /// Forgets the declared names, reporting and unbinding every binding among them
+    /// that still refers to a metavariable which was never bound,
+    /// so binding that metavariable later on does not give it a meaning.
+    /// Bindings that only referred to unbound ones are unbound as well.
+    pub fn check_declared(&mut self) -> Result<(), Error> {
+        let declared = std::mem::take(&mut self.evaluator.declared);
+        let mut errors = Vec::new();
+        loop {
+            let unbound: Vec<(&String, EvalError)> = declared.iter()
+                .filter_map(|name| self.evaluator.env().get(name).map(|definition| (name, definition)))
+                .filter_map(|(name, definition)| self.evaluator.check_bound(definition).err().map(|error| (name, error)))
+                .collect();
+            if unbound.is_empty() { break; }
+            for (name, error) in unbound {
+                self.evaluator.remove(name);
+                errors.push(Error::Eval(error));
+            }
+        }
+        errors.sort_by_key(|error| error.span().map(|span| span.start));
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(Error::from_all(errors)),
+        }
+    }
+
+    /// Binds `name` to the value of the expression in `input`, returning that value.
+    pub fn define(&mut self, name: &str, input: &str) -> Result<Expr, Error> {
+        let expr = self.parse_expr(input)?;
+        let span = expr.span();
+        self.eval(Expr::Binding(name.to_string(), Box::new(expr), span))
+    }
+
+    /// Binds every definition of a source file, see `load_str`.
+    pub fn load_file(&mut self, path: &str) -> Result<Vec<String>, Error> {
+        let source = fs::read_to_string(path).map_err(|error| Error::Io { path: path.to_string(), error })?;
+        self.load_str(path, &source)
+    }
+
+    /// Binds every definition in `source`, returning their names.
+    ///
+    /// Only bindings `Name = expr` are allowed, see `parse_source` for the format.
+    /// Errors are reported as `file:line:col: message`. A bad statement does not stop the load,
+    /// every other definition is still bound and all errors are reported together.
+    /// Definitions may refer to names defined later in the file.
+    pub fn load_str(&mut self, file: &str, source: &str) -> Result<Vec<String>, Error> {
+        let (statements, mut errors) = self.parse_source_recovering(source);
+        self.declare(&statements);
+        let mut names = Vec::new();
+        for statement in statements {
+            let name = match &statement.expr {
+                Expr::Binding(name, _, _) => name.clone(),
+                _ => {
+                    errors.push(Error::ExpectedBinding { span: statement.span });
+                    continue;
+                }
+            };
+            match self.eval_statement(&statement) {
+                Ok(_) => names.push(name),
+                Err(error) => errors.push(error),
+            }
+        }
+        if let Err(error) = self.check_declared() {
+            errors.push(error);
+        }
+        match errors.is_empty() {
+            true => Ok(names),
+            false => Err(Error::from_all(errors).in_file(file, source)),
+        }
+    }
+
+    /// Parses a source file of statements, i.e. bindings or expressions,
+    /// separated by newlines or `;`. Inside parentheses or brackets newlines are whitespace,
+    /// so a definition can span several lines, unless a line starts with a binding, e.g. `Name =`,
+    /// which ends an unclosed one instead.
+    /// Blank lines and comments are skipped, see `parse_source_with_comments` to keep the comments.
+    /// Every error in the file is reported, not only the first.
+    pub fn parse_source(&self, file: &str, source: &str) -> Result<Program, Error> {
+        let (program, errors) = self.parse_source_recovering(source);
+        match errors.is_empty() {
+            true => Ok(program),
+            false => Err(Error::from_all(errors).in_file(file, source)),
+        }
+    }
+
+    /// Like `parse_source`, also returning the comments of `source` in order,
+    /// e.g. for a formatter to put back between the statements by their spans.
+    pub fn parse_source_with_comments(&self, file: &str, source: &str) -> Result<(Program, Vec<Comment>), Error> {
+        let (program, comments, errors) = self.parse_source_parts(source);
+        match errors.is_empty() {
+            true => Ok((program, comments)),
+            false => Err(Error::from_all(errors).in_file(file, source)),
+        }
+    }
+
+    /// Like `parse_source`, but returns the statements that did parse along with the errors.
+    pub fn parse_source_recovering(&self, source: &str) -> (Program, Vec<Error>) {
+        let (program, _, errors) = self.parse_source_parts(source);
+        (program, errors)
+    }
+
+    fn parse_source_parts(&self, source: &str) -> (Program, Vec<Comment>, Vec<Error>) {
+        let mut lexer = Lexer::new();
+        let (tokens, lex_errors) = lexer.lex_recovering(source);
+        let (program, parse_errors) = Parser::new(tokens).parse_recovering();
+        let mut errors: Vec<Error> = lex_errors.into_iter().map(Error::Lex)
+            .chain(parse_errors.into_iter().map(Error::Parse))
+            .collect();
+        errors.sort_by_key(|error| error.span().map(|span| span.start));
+        (program, lexer.comments().to_vec(), errors)
+    }
+
+    /// Evaluates a parsed statement, errors of reduction are reported at the statement.
+    pub fn eval_statement(&mut self, statement: &Statement) -> Result<Expr, Error> {
+        let name = match &statement.expr {
+            Expr::Binding(name, _, _) => Some(name.clone()),
+            _ => None,
+        };
+        self.evaluator.evaluate(statement.expr.clone()).map_err(|error| match error {
+            EvalError::LimitReached { .. } => Error::Statement { name, span: statement.span, error: Box::new(error.into()) },
+            error => error.into(),
+        })
+    }
+
+    pub fn strategy(&self) -> ReductionStrategy {
+        self.evaluator.strategy
+    }
+
+    pub fn set_strategy(&mut self, strategy: ReductionStrategy) {
+        self.evaluator.strategy = strategy;
+    }
+
+    pub fn eta(&self) -> bool {
+        self.evaluator.eta
+    }
+
+    /// Whether eta redexes are contracted along with beta redexes.
+    pub fn set_eta(&mut self, eta: bool) {
+        self.evaluator.eta = eta;
+    }
+
+    pub fn fuel(&self) -> Option<usize> {
+        self.evaluator.fuel
+    }
+
+    /// Limits the number of reduction steps, `None` removes the limit.
+    pub fn set_fuel(&mut self, fuel: Option<usize>) {
+        self.evaluator.fuel = fuel;
+    }
+
+    pub fn fold(&self) -> bool {
+        self.evaluator.fold
+    }
+
+    /// Whether results show subterms equal to a binding by the binding's name, e.g. `K I` -> `KI`,
+    /// or in full, with every binding they refer to expanded.
+    pub fn set_fold(&mut self, fold: bool) {
+        self.evaluator.fold = fold;
+    }
+
+    pub fn notation(&self) -> Notation {
+        self.notation
+    }
+
+    /// Whether expressions are shown with `\` or `λ`, see `Expr::notated`.
+    /// Input accepts both either way.
+    pub fn set_notation(&mut self, notation: Notation) {
+        self.notation = notation;
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.evaluator.max_depth
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.evaluator.max_depth = max_depth;
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.evaluator.max_size
+    }
+
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.evaluator.max_size = max_size;
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Expr> {
+        self.evaluator.env().get(name)
+    }
+
+    pub fn bindings(&self) -> &HashMap<String, Expr> {
+        self.evaluator.env()
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    pub fn evaluator_mut(&mut self) -> &mut Evaluator {
+        &mut self.evaluator
+    }
+}